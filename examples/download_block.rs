@@ -0,0 +1,65 @@
+//! Connects to a node, performs the handshake and downloads a single block.
+//!
+//! ```text
+//! cargo run --example download_block -- <NODE_ADDR:PORT> [BLOCK_HASH]
+//! ```
+//!
+//! Downloads the genesis block when no hash is given.
+
+use bitcoin_handshake::*;
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+async fn wait_for(
+    peer: &mut Peer<tokio::net::TcpStream>,
+    command: Command,
+) -> Result<Message, BitcoinMessageError> {
+    loop {
+        match peer.recv().await {
+            Ok(m) if *m.command() == command => return Ok(m),
+            Ok(_) | Err(BitcoinMessageError::CommandNameUnknown(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let addr: SocketAddr = args.next().ok_or("missing node address")?.parse()?;
+    let hash: Hash = args.next().as_deref().unwrap_or(GENESIS_HASH).parse()?;
+
+    let mut peer = Peer::connect(addr, Network::Mainnet).await?;
+    let version = VersionData::new(
+        ServiceIdentifier::NODE_NETWORK,
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64,
+        ServiceIdentifier::NODE_NETWORK,
+        addr,
+        ServiceIdentifier::NODE_NETWORK,
+        peer.get_ref().local_addr()?,
+        "".to_string(),
+        0,
+        false,
+    );
+    peer.send(Command::Version, Payload::Version(version))
+        .await?;
+    wait_for(&mut peer, Command::Version).await?;
+    peer.send(Command::VerAck, Payload::Empty).await?;
+    wait_for(&mut peer, Command::VerAck).await?;
+
+    let block = download_block(&mut peer, hash, Duration::from_secs(30)).await?;
+    println!(
+        "block {}: {} transactions, timestamp {}",
+        hash,
+        block.transactions().len(),
+        block.header().timestamp()
+    );
+
+    Ok(())
+}
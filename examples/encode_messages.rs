@@ -0,0 +1,44 @@
+//! Encodes and decodes a `version`/`verack` pair using only the serialization types.
+//!
+//! This example does not depend on any of the networking code, so it doubles as a check that the
+//! library builds for `wasm32-unknown-unknown`:
+//!
+//! ```text
+//! cargo build --example encode_messages --target wasm32-unknown-unknown --no-default-features --features std
+//! ```
+
+use bitcoin_handshake::*;
+use std::net::{Ipv4Addr, SocketAddr};
+
+fn main() -> Result<(), BitcoinMessageError> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, PORT_MAINNET));
+    // a fixed nonce, so this builds without the `rand` feature
+    let version_data = VersionData::with_nonce(
+        0x0123456789abcdef,
+        ServiceIdentifier::NODE_NETWORK,
+        0,
+        ServiceIdentifier::NODE_NETWORK,
+        addr,
+        ServiceIdentifier::NODE_NETWORK,
+        addr,
+        "/encode_messages/".to_string(),
+        0,
+        false,
+    );
+    let messages = [
+        Message::new(
+            START_STRING_MAINNET,
+            Command::Version,
+            Payload::Version(version_data),
+        ),
+        Message::new(START_STRING_MAINNET, Command::VerAck, Payload::Empty),
+    ];
+
+    for message in messages {
+        let bytes = message.to_bytes()?;
+        let decoded = Message::from_bytes(&mut bytes.as_slice())?;
+        println!("{} ({} bytes)", decoded.command(), bytes.len());
+    }
+
+    Ok(())
+}
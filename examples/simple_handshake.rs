@@ -0,0 +1,37 @@
+//! Performs the handshake with a node, using only the library API, and prints its version.
+//!
+//! ```text
+//! cargo run --example simple_handshake
+//! ```
+
+use bitcoin_handshake::*;
+use std::{net::SocketAddr, time::SystemTime};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // a node running locally
+    let addr = SocketAddr::from(([127, 0, 0, 1], PORT_MAINNET));
+
+    let mut peer = Peer::connect(addr, Network::Mainnet).await?;
+    let version = VersionData::new(
+        ServiceIdentifier::NODE_NETWORK,
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64,
+        ServiceIdentifier::NODE_NETWORK,
+        addr,
+        ServiceIdentifier::NODE_NETWORK,
+        peer.get_ref().local_addr()?,
+        "".to_string(),
+        0,
+        false,
+    );
+    peer.send_version(version).await?;
+    let version = peer.recv_version().await?;
+    peer.send_verack().await?;
+    peer.recv_verack().await?;
+
+    println!("{} (version {})", version.user_agent(), version.version());
+
+    Ok(())
+}
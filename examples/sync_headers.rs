@@ -0,0 +1,61 @@
+//! Connects to a node, performs the handshake and downloads block headers.
+//!
+//! ```text
+//! cargo run --example sync_headers -- <NODE_ADDR:PORT> [LOCATOR_FILE]
+//! ```
+//!
+//! The block locator is persisted in `LOCATOR_FILE` (`locator.txt` by default), one hash per line, so subsequent
+//! runs continue where the previous one stopped. The first run starts at the genesis block.
+
+use bitcoin_handshake::*;
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let addr: SocketAddr = args.next().ok_or("missing node address")?.parse()?;
+    let locator_file = args.next().unwrap_or_else(|| "locator.txt".to_string());
+
+    let locator: Vec<Hash> = match std::fs::read_to_string(&locator_file) {
+        Ok(s) => s.lines().map(str::parse).collect::<Result<_, _>>()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![GENESIS_HASH.parse()?],
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut peer = Peer::connect(addr, Network::Mainnet).await?;
+    let version = VersionData::new(
+        ServiceIdentifier::NODE_NETWORK,
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64,
+        ServiceIdentifier::NODE_NETWORK,
+        addr,
+        ServiceIdentifier::NODE_NETWORK,
+        peer.get_ref().local_addr()?,
+        "".to_string(),
+        0,
+        false,
+    );
+    peer.send_version(version).await?;
+    peer.recv_version().await?;
+    peer.send_verack().await?;
+    peer.recv_verack().await?;
+
+    let headers = sync_headers(&mut peer, locator.clone(), Duration::from_secs(30)).await?;
+    println!("received {} headers", headers.len());
+
+    // the new locator covers the synced headers, falling back to the old one for the blocks before them
+    let mut chain: Vec<Hash> = locator.into_iter().rev().collect();
+    chain.extend(headers.iter().map(|h| h.block_hash()));
+    let locator = block_locator(&chain);
+    let contents: Vec<String> = locator.iter().map(Hash::to_string).collect();
+    std::fs::write(&locator_file, contents.join("\n"))?;
+    println!("tip: {}", locator[0]);
+
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use crate::{
+    enums::ServiceIdentifier,
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    message::{ipv6, BitcoinDeserialize, BitcoinSerialize},
+    types::{read_vec, write_vec, VarInt},
+};
+use alloc::{collections::BTreeMap, vec::Vec};
+use byteorder::{BigEndian, LittleEndian};
+use core::net::{Ipv6Addr, SocketAddr};
+#[cfg(feature = "getset")]
+use getset::Getters;
+
+/// Size of a serialized [`TimestampedAddress`], in bytes.
+pub const TIMESTAMPED_ADDRESS_SIZE: usize = 30;
+
+/// Maximum number of [`TimestampedAddress`]es in a single `addr` message.
+pub const MAX_ADDR_LEN: usize = 1000;
+
+/// Address of a node, as announced in `addr` messages. See [bitcoin docs](https://developer.bitcoin.org/reference/p2p_networking.html#addr).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct TimestampedAddress {
+    /// When the node was last seen, as a Unix timestamp.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    timestamp: u32,
+
+    /// Services the node advertised.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    services: ServiceIdentifier,
+
+    /// Address of the node. IPv4-mapped IPv6 addresses are decoded as IPv4.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    socket_address: SocketAddr,
+}
+
+#[cfg(not(feature = "getset"))]
+impl TimestampedAddress {
+    /// When the node was last seen, as a Unix timestamp.
+    #[inline(always)]
+    pub fn timestamp(&self) -> &u32 {
+        &self.timestamp
+    }
+
+    /// Services the node advertised.
+    #[inline(always)]
+    pub fn services(&self) -> &ServiceIdentifier {
+        &self.services
+    }
+
+    /// Address of the node. IPv4-mapped IPv6 addresses are decoded as IPv4.
+    #[inline(always)]
+    pub fn socket_address(&self) -> &SocketAddr {
+        &self.socket_address
+    }
+}
+
+impl TimestampedAddress {
+    /// Creates new [`TimestampedAddress`].
+    pub fn new(
+        timestamp: u32,
+        services: ServiceIdentifier,
+        socket_address: impl Into<SocketAddr>,
+    ) -> Self {
+        Self {
+            timestamp,
+            services,
+            socket_address: socket_address.into(),
+        }
+    }
+
+    /// Creates new [`TimestampedAddress`] advertising `services`, with a zero timestamp.
+    pub fn with_services(
+        socket_address: impl Into<SocketAddr>,
+        services: ServiceIdentifier,
+    ) -> Self {
+        Self::new(0, services, socket_address)
+    }
+}
+
+impl From<SocketAddr> for TimestampedAddress {
+    /// Creates [`TimestampedAddress`] with [`ServiceIdentifier::UNNAMED`] services and a zero timestamp.
+    fn from(socket_address: SocketAddr) -> Self {
+        Self::with_services(socket_address, ServiceIdentifier::UNNAMED)
+    }
+}
+
+impl BitcoinSerialize for TimestampedAddress {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(TIMESTAMPED_ADDRESS_SIZE);
+        buf.write_u32::<LittleEndian>(self.timestamp)?;
+        buf.write_u64::<LittleEndian>(self.services.bits())?;
+        buf.write_all(&ipv6(&self.socket_address).octets())?;
+        buf.write_u16::<BigEndian>(self.socket_address.port())?;
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for TimestampedAddress {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let timestamp = data.read_u32::<LittleEndian>()?;
+        let services = ServiceIdentifier::from_bits_retain(data.read_u64::<LittleEndian>()?);
+        let ip: Ipv6Addr = data.read_u128::<BigEndian>()?.into();
+        let port = data.read_u16::<BigEndian>()?;
+
+        Ok(Self {
+            timestamp,
+            services,
+            socket_address: (ip.to_canonical(), port).into(),
+        })
+    }
+}
+
+/// Removes duplicate addresses from `addrs`, keeping the most recent entry of each, in the order of first
+/// appearance.
+pub fn dedup_by_socket_addr(addrs: &mut Vec<TimestampedAddress>) {
+    let mut deduped: Vec<TimestampedAddress> = Vec::with_capacity(addrs.len());
+    let mut positions: BTreeMap<SocketAddr, usize> = BTreeMap::new();
+    for addr in addrs.drain(..) {
+        match positions.get(&addr.socket_address) {
+            Some(&i) if deduped[i].timestamp < addr.timestamp => deduped[i] = addr,
+            Some(_) => {}
+            None => {
+                positions.insert(addr.socket_address, deduped.len());
+                deduped.push(addr);
+            }
+        }
+    }
+    *addrs = deduped;
+}
+
+/// Sorts `addrs` by timestamp, the most recently seen first.
+pub fn sort_by_recency(addrs: &mut [TimestampedAddress]) {
+    addrs.sort_by_key(|a| core::cmp::Reverse(a.timestamp));
+}
+
+/// Serializes an address list, as used by the `addr` message.
+pub(crate) fn addr_to_bytes(items: &[TimestampedAddress]) -> Result<Vec<u8>, BitcoinMessageError> {
+    let mut buf = Vec::with_capacity(addr_len(items));
+    write_vec(&mut buf, items, MAX_ADDR_LEN, |count| {
+        BitcoinMessageError::AddrTooLarge { count }
+    })?;
+
+    Ok(buf)
+}
+
+/// Returns the serialized length of an address list.
+pub(crate) fn addr_len(items: &[TimestampedAddress]) -> usize {
+    VarInt::from(items.len()).len() + items.len() * TIMESTAMPED_ADDRESS_SIZE
+}
+
+/// Deserializes an address list, as used by the `addr` message.
+pub(crate) fn addr_from_bytes(
+    data: &mut impl Read,
+) -> Result<Vec<TimestampedAddress>, BitcoinMessageError> {
+    read_vec(data, MAX_ADDR_LEN, |count| {
+        BitcoinMessageError::AddrTooLarge { count }
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::utils::arbitrary_wire_addr;
+    use hex_literal::hex;
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    impl Arbitrary for TimestampedAddress {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            Self::new(
+                u32::arbitrary(g),
+                ServiceIdentifier::arbitrary(g),
+                arbitrary_wire_addr(g),
+            )
+        }
+    }
+
+    fn addr(timestamp: u32, last_octet: u8) -> TimestampedAddress {
+        TimestampedAddress::new(
+            timestamp,
+            ServiceIdentifier::NODE_NETWORK,
+            ([10, 0, 0, last_octet], 8333),
+        )
+    }
+
+    #[quickcheck]
+    fn addr_roundtrip(items: Vec<TimestampedAddress>) -> bool {
+        let items: Vec<_> = items.into_iter().take(MAX_ADDR_LEN).collect();
+        let bytes = addr_to_bytes(&items).unwrap();
+
+        bytes.len() == addr_len(&items) && addr_from_bytes(&mut bytes.as_slice()).unwrap() == items
+    }
+
+    #[test]
+    fn timestamped_address_serialization() {
+        let x = TimestampedAddress::new(
+            0x01020304,
+            ServiceIdentifier::NODE_NETWORK,
+            ([192, 168, 1, 2], 8333),
+        );
+
+        assert_eq!(
+            x.to_bytes().unwrap(),
+            hex!("04030201010000000000000000000000000000000000ffffc0a80102208d")
+        );
+    }
+
+    #[test]
+    fn from_socket_addr_has_no_services() {
+        let socket_address = SocketAddr::from(([192, 168, 1, 2], 8333));
+
+        let x = TimestampedAddress::from(socket_address);
+
+        assert_eq!(*x.services(), ServiceIdentifier::UNNAMED);
+        assert_eq!(*x.socket_address(), socket_address);
+        assert_eq!(*x.timestamp(), 0);
+        assert_eq!(
+            TimestampedAddress::with_services(socket_address, ServiceIdentifier::NODE_NETWORK),
+            TimestampedAddress::new(0, ServiceIdentifier::NODE_NETWORK, socket_address)
+        );
+    }
+
+    #[test]
+    fn addr_over_limit() {
+        let items = vec![addr(0, 1); MAX_ADDR_LEN + 1];
+
+        assert!(matches!(
+            addr_to_bytes(&items),
+            Err(BitcoinMessageError::AddrTooLarge { count }) if count == MAX_ADDR_LEN + 1
+        ));
+    }
+
+    #[test]
+    fn dedup_keeps_most_recent() {
+        let mut addrs = vec![addr(10, 1), addr(20, 2), addr(30, 1), addr(5, 2)];
+
+        dedup_by_socket_addr(&mut addrs);
+
+        assert_eq!(addrs, vec![addr(30, 1), addr(20, 2)]);
+    }
+
+    #[test]
+    fn sort_puts_most_recent_first() {
+        let mut addrs = vec![addr(10, 1), addr(30, 2), addr(20, 3)];
+
+        sort_by_recency(&mut addrs);
+
+        assert_eq!(addrs, vec![addr(30, 2), addr(20, 3), addr(10, 1)]);
+    }
+}
@@ -2,16 +2,25 @@
 #![deny(clippy::expect_used)]
 
 use bitcoin_handshake::*;
-use clap::Parser;
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use color_eyre::eyre::{eyre, Result};
-use futures::future::join_all;
+use futures::{future::join_all, stream::FuturesUnordered, Future, StreamExt};
+use serde::{Deserialize, Deserializer};
 use std::{
-    net::SocketAddr,
-    time::{Duration, SystemTime},
+    cell::Cell,
+    collections::HashSet,
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    io::{AsyncRead, AsyncWrite, ReadBuf},
     net::{lookup_host, TcpStream},
+    sync::Semaphore,
     time::timeout,
 };
 use tracing::instrument;
@@ -19,33 +28,283 @@ use tracing::instrument;
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Bitcoin DNS seed to connect to.
-    dns_seed: String,
+    /// Bitcoin DNS seeds to connect to. Required, unless set in `--config` or `--use-builtin-seeds` is given.
+    dns_seeds: Vec<String>,
 
-    /// TCP port to connect to.
-    #[arg(short, long, default_value_t = PORT_MAINNET)]
-    port: u16,
+    /// Resolve all the well-known DNS seeds of `--network`, merging their addresses (and those of `DNS_SEEDS`, if
+    /// given).
+    #[arg(long)]
+    use_builtin_seeds: bool,
 
-    /// Handshake timeout, in seconds.
+    /// Load settings from a TOML file, with keys named like the options (e.g. `protocol_version`), and `dns_seeds`
+    /// as an array. Options given on the command line take precedence.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Network to handshake on.
+    #[arg(long, value_enum, default_value_t = ScanNetwork::Mainnet)]
+    network: ScanNetwork,
+
+    /// TCP port to connect to. Defaults to the one of `--network`.
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    /// Handshake timeout, in seconds. Starts once the TCP connection is established.
     #[arg(short, long, default_value_t = 10)]
     timeout: u64,
+
+    /// TCP connect timeout, in seconds.
+    #[arg(long, default_value_t = 3)]
+    connect_timeout: u64,
+
+    /// Timeout of the `version` round-trip, in seconds. Defaults to half of `--timeout`.
+    #[arg(long)]
+    version_timeout: Option<u64>,
+
+    /// Timeout of the `verack` round-trip, in seconds. Defaults to half of `--timeout`.
+    #[arg(long)]
+    verack_timeout: Option<u64>,
+
+    /// Log debug messages too, e.g. each handshake phase with the time elapsed since connecting.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Hex-print the raw bytes of every sent and received frame.
+    #[arg(long)]
+    dump_raw: bool,
+
+    /// Services to advertise, e.g. `NODE_NETWORK|NODE_WITNESS`.
+    #[arg(long, default_value = "NODE_NETWORK")]
+    services: ServiceIdentifier,
+
+    /// Protocol version to advertise. Peers may reject old versions.
+    #[arg(long, default_value_t = PROTOCOL_VERSION)]
+    protocol_version: i32,
+
+    /// User agent to advertise, e.g. `/bitcoin-handshake:0.1.0/`.
+    #[arg(long, default_value = "")]
+    user_agent: String,
+
+    /// Print a JSON object per peer to stdout as soon as its handshake finishes. Logs go to stderr instead.
+    #[arg(long)]
+    json_lines: bool,
+
+    /// Only exchange `version` messages, and disconnect without sending `verack`, reporting what the peer advertised.
+    #[arg(long)]
+    probe_only: bool,
+
+    /// Stop after this many successful handshakes, cancelling the remaining ones.
+    #[arg(long)]
+    count: Option<usize>,
+
+    /// Deliberately violate the protocol instead of handshaking, reporting whether the peer disconnects or
+    /// tolerates it. `verack-first` sends a lone `verack`, without a `version`.
+    #[arg(long, value_enum)]
+    misbehave: Option<Misbehavior>,
+
+    /// Maximum number of concurrent handshakes per address family (IPv4 and IPv6). Attempts of both families are
+    /// interleaved, so timeouts in one family don't hold up the other. Unlimited by default.
+    #[arg(long)]
+    max_per_family: Option<usize>,
+
+    /// Only connect to addresses in this CIDR range, e.g. `203.0.113.0/24`. May be repeated.
+    #[arg(long)]
+    allow_cidr: Vec<Cidr>,
+
+    /// Never connect to addresses in this CIDR range, e.g. `10.0.0.0/8`. May be repeated. Takes precedence over
+    /// `--allow-cidr`.
+    #[arg(long)]
+    deny_cidr: Vec<Cidr>,
+}
+
+impl Args {
+    fn network(&self) -> Network {
+        self.network.into()
+    }
+
+    fn port(&self) -> u16 {
+        self.port.unwrap_or(self.network().default_port())
+    }
+}
+
+/// Networks selectable with `--network`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ScanNetwork {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl From<ScanNetwork> for Network {
+    fn from(network: ScanNetwork) -> Self {
+        match network {
+            ScanNetwork::Mainnet => Network::Mainnet,
+            ScanNetwork::Testnet => Network::Testnet,
+            ScanNetwork::Signet => Network::Signet,
+            ScanNetwork::Regtest => Network::Regtest,
+        }
+    }
+}
+
+/// IP address range, e.g. `10.0.0.0/8` or `2001:db8::/32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cidr {
+    network: IpAddr,
+    prefix_len: u32,
+}
+
+impl Cidr {
+    /// Returns `true` if `ip` is in this range. IPv4-mapped IPv6 addresses match as IPv4.
+    fn contains(&self, ip: IpAddr) -> bool {
+        let (network, ip, bits) = match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(n), IpAddr::V4(ip)) => (u32::from(n).into(), u32::from(ip).into(), 32),
+            (IpAddr::V6(n), IpAddr::V6(ip)) => (u128::from(n), u128::from(ip), 128),
+            _ => return false,
+        };
+        let mask = u128::MAX.checked_shl(bits - self.prefix_len).unwrap_or(0);
+
+        network & mask == ip & mask
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (network, prefix_len) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected `address/prefix`, got `{}`", s))?;
+        let network: IpAddr = network.parse().map_err(|e| format!("{}", e))?;
+        let prefix_len: u32 = prefix_len.parse().map_err(|e| format!("{}", e))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max {
+            return Err(format!("prefix length {} is over {}", prefix_len, max));
+        }
+        // IPv4-mapped ranges are matched as IPv4, so the prefix has to cover the mapping
+        let (network, prefix_len) = match (network, network.to_canonical()) {
+            (IpAddr::V6(_), v4 @ IpAddr::V4(_)) => match prefix_len.checked_sub(96) {
+                Some(prefix_len) => (v4, prefix_len),
+                None => {
+                    return Err(format!(
+                        "prefix length {} of an IPv4-mapped range is under 96",
+                        prefix_len
+                    ))
+                }
+            },
+            (network, _) => (network, prefix_len),
+        };
+
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+/// Returns `true` if `ip` may be connected to: it's not in any of `deny`, and in one of `allow`, unless empty.
+fn is_allowed(ip: IpAddr, allow: &[Cidr], deny: &[Cidr]) -> bool {
+    !deny.iter().any(|c| c.contains(ip))
+        && (allow.is_empty() || allow.iter().any(|c| c.contains(ip)))
+}
+
+/// Protocol violations of `--misbehave`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Misbehavior {
+    // `verack` before (and without) `version`
+    VerackFirst,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    let args = parse_args()?;
+    let level = match args.verbose {
+        true => tracing::Level::DEBUG,
+        false => tracing::Level::INFO,
+    };
+    let subscriber = tracing_subscriber::fmt().with_max_level(level);
+    if args.json_lines {
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
     color_eyre::install()?;
-    let args = Args::parse();
-
-    tracing::info!("Resolving DNS seed `{}`", args.dns_seed);
 
-    let resolved_addrs: Vec<_> = lookup_host((args.dns_seed, args.port)).await?.collect();
+    if args.user_agent.len() > MAX_USER_AGENT_LEN {
+        return Err(eyre!(
+            "`--user-agent` is longer than {} bytes",
+            MAX_USER_AGENT_LEN
+        ));
+    }
+    let mut seeds: Vec<&str> = args.dns_seeds.iter().map(String::as_str).collect();
+    if args.use_builtin_seeds {
+        seeds.extend(args.network().dns_seeds());
+    }
+    let resolved_addrs = match seeds.as_slice() {
+        [] => {
+            return Err(eyre!(
+            "no DNS seed given, on the command line or in `--config`, and no `--use-builtin-seeds`"
+        ))
+        }
+        [dns_seed] => {
+            tracing::info!("Resolving DNS seed `{}`", dns_seed);
+            lookup_host((*dns_seed, args.port())).await?.collect()
+        }
+        seeds => resolve_all(seeds, args.port()).await,
+    };
     tracing::info!(
         "Resolved {} addreses. Starting handshakes...",
         resolved_addrs.len()
     );
 
-    let results = join_all(resolved_addrs.iter().map(|t| process(*t, args.timeout))).await;
+    let resolved_count = resolved_addrs.len();
+    let resolved_addrs: Vec<_> = resolved_addrs
+        .into_iter()
+        .filter(|a| is_allowed(a.ip(), &args.allow_cidr, &args.deny_cidr))
+        .collect();
+    if resolved_addrs.len() < resolved_count {
+        tracing::info!(
+            "Skipping {} addresses outside of `--allow-cidr`, or in `--deny-cidr`",
+            resolved_count - resolved_addrs.len()
+        );
+    }
+
+    if args.max_per_family == Some(0) {
+        return Err(eyre!("`--max-per-family` must be at least 1"));
+    }
+    let resolved_addrs = interleave_families(resolved_addrs);
+    let permits = args.max_per_family.unwrap_or(Semaphore::MAX_PERMITS);
+    let (ipv4_slots, ipv6_slots) = (Semaphore::new(permits), Semaphore::new(permits));
+
+    // each handshake needs a read buffer, reuse them as handshakes finish
+    let pool = BufferPool::new(resolved_addrs.len());
+    let handshakes = resolved_addrs.iter().map(|t| {
+        let slots = match t {
+            SocketAddr::V4(_) => &ipv4_slots,
+            SocketAddr::V6(_) => &ipv6_slots,
+        };
+        let handshake = process(*t, &args, &pool);
+        async move {
+            let (result, elapsed) = match slots.acquire().await {
+                Ok(_permit) => {
+                    let started = Instant::now();
+                    (handshake.await, started.elapsed())
+                }
+                Err(e) => (Err(e.into()), Duration::ZERO),
+            };
+            Attempt {
+                target: *t,
+                result,
+                elapsed,
+            }
+        }
+    });
+    let attempts = match args.count {
+        Some(count) => until_successes(handshakes, count).await,
+        None => join_all(handshakes).await,
+    };
+    let results: Vec<_> = attempts.iter().map(|a| &a.result).collect();
 
     let fails = results.iter().filter(|x| x.is_err()).count();
     let partial_ok = results
@@ -54,147 +313,883 @@ async fn main() -> Result<()> {
         .count();
     let ok = results
         .iter()
-        .filter(|x| matches!(x, Ok(MessageExchangeResult::Ok)))
+        .filter(|x| matches!(x, Ok(MessageExchangeResult::Ok { .. })))
+        .count();
+    let rejected = results
+        .iter()
+        .filter(|x| matches!(x, Ok(MessageExchangeResult::Rejected(_))))
         .count();
+    let probed = results
+        .iter()
+        .filter(|x| matches!(x, Ok(MessageExchangeResult::Probed { .. })))
+        .count();
+    let misbehaved = |disconnected| {
+        results
+            .iter()
+            .filter(|x| matches!(x, Ok(MessageExchangeResult::Misbehaved { disconnected: d }) if *d == disconnected))
+            .count()
+    };
 
+    if args.misbehave.is_some() {
+        tracing::info!(
+            "Finished! Misbehavior results: {} DISCONNECTED | {} TOLERATED | {} FAILED",
+            misbehaved(true),
+            misbehaved(false),
+            fails
+        );
+    } else if args.probe_only {
+        tracing::info!(
+            "Finished! Probe results: {} PROBED | {} REJECTED | {} FAILED",
+            probed,
+            rejected,
+            fails
+        );
+    } else {
+        tracing::info!(
+            "Finished! Handshake results: {} OK | {} PARTIALLY OK | {} REJECTED | {} FAILED",
+            ok,
+            partial_ok,
+            rejected,
+            fails
+        );
+    }
+    let family_successes = |ipv4| {
+        let attempts = attempts.iter().filter(|a| a.target.is_ipv4() == ipv4);
+        let successes = attempts.clone().filter(|a| is_success(&a.result)).count();
+        (successes, attempts.count())
+    };
+    let (ipv4_ok, ipv4_total) = family_successes(true);
+    let (ipv6_ok, ipv6_total) = family_successes(false);
     tracing::info!(
-        "Finished! Handshake results: {} OK | {} PARTIALLY OK | {} FAILED",
-        ok,
-        partial_ok,
-        fails
+        "Successes by address family: {} of {} IPv4 | {} of {} IPv6",
+        ipv4_ok,
+        ipv4_total,
+        ipv6_ok,
+        ipv6_total
     );
+    let mut latencies: Vec<_> = attempts
+        .iter()
+        .filter(|a| is_success(&a.result))
+        .map(|a| a.elapsed)
+        .collect();
+    latencies.sort();
+    if let (Some(min), Some(max)) = (latencies.first(), latencies.last()) {
+        tracing::info!(
+            "Latency of successes: min {:.1?} | p50 {:.1?} | p90 {:.1?} | p99 {:.1?} | max {:.1?}",
+            min,
+            percentile(&latencies, 50),
+            percentile(&latencies, 90),
+            percentile(&latencies, 99),
+            max
+        );
+    }
+    if let Some(count) = args.count {
+        tracing::info!(
+            "Finished {} of {} handshakes, for a target of {} successful",
+            results.len(),
+            resolved_addrs.len(),
+            count
+        );
+    }
 
     Ok(())
 }
 
-#[instrument(name = "handshake", skip(timeout_secs))]
-async fn process(target: SocketAddr, timeout_secs: u64) -> Result<MessageExchangeResult> {
-    let result = timeout(Duration::from_secs(timeout_secs), process_inner(target)).await;
+/// Resolves all `seeds` concurrently, merging their addresses without duplicates. Seeds failing to resolve are
+/// logged and skipped.
+async fn resolve_all(seeds: &[&str], port: u16) -> Vec<SocketAddr> {
+    tracing::info!("Resolving {} DNS seeds", seeds.len());
+    let lookups = seeds.iter().map(|seed| async move {
+        match lookup_host((*seed, port)).await {
+            Ok(addrs) => addrs.collect(),
+            Err(e) => {
+                tracing::warn!("failed to resolve DNS seed `{}`: {}", seed, e);
+                Vec::new()
+            }
+        }
+    });
+
+    dedup_addrs(join_all(lookups).await.into_iter().flatten())
+}
+
+/// Collects `addrs`, dropping repeated ones while keeping the order of first appearance.
+fn dedup_addrs(addrs: impl Iterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    let mut seen = HashSet::new();
+
+    addrs.filter(|a| seen.insert(*a)).collect()
+}
+
+/// Reorders `addrs` to alternate between IPv4 and IPv6, keeping the order within each family. Leftovers of the
+/// larger family go last.
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (ipv4, ipv6): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv4);
+    let (mut ipv4, mut ipv6) = (ipv4.into_iter(), ipv6.into_iter());
+    let mut interleaved = Vec::with_capacity(ipv4.len() + ipv6.len());
+    loop {
+        match (ipv4.next(), ipv6.next()) {
+            (None, None) => break,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+
+    interleaved
+}
+
+/// Finished handshake with `target`.
+struct Attempt {
+    target: SocketAddr,
+    result: Result<MessageExchangeResult>,
+    /// From the start of connecting, not counting the wait for a `--max-per-family` slot.
+    elapsed: Duration,
+}
+
+/// Returns the `p`th percentile of the ascending `sorted` durations, by the nearest-rank method: the smallest value
+/// at or above which `p` percent of the values lie, without interpolation. `sorted` must not be empty.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+
+    sorted[rank - 1]
+}
+
+/// Returns `true` if the handshake (or probe) succeeded.
+fn is_success(result: &Result<MessageExchangeResult>) -> bool {
+    matches!(
+        result,
+        Ok(MessageExchangeResult::Ok { .. }
+            | MessageExchangeResult::Probed { .. }
+            | MessageExchangeResult::Misbehaved { .. })
+    )
+}
 
-    // unwrap the timeout result
-    let result = match result {
-        Ok(r) => r,
-        Err(e) => Err(e.into()),
+/// Runs `handshakes` concurrently until `count` of them succeed, then drops (cancels) the rest. Returns the results
+/// of the finished handshakes.
+async fn until_successes(
+    handshakes: impl Iterator<Item = impl Future<Output = Attempt>>,
+    count: usize,
+) -> Vec<Attempt> {
+    let mut pending: FuturesUnordered<_> = handshakes.collect();
+    let mut results = Vec::new();
+    let mut successes = 0;
+    while successes < count {
+        let Some(attempt) = pending.next().await else {
+            break;
+        };
+        if is_success(&attempt.result) {
+            successes += 1;
+        }
+        results.push(attempt);
+    }
+
+    results
+}
+
+/// Parses the command line, merging in the `--config` file if given.
+fn parse_args() -> Result<Args> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    if let Some(path) = &args.config {
+        let config: ScanConfig = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| eyre!("invalid config `{}`: {}", path.display(), e))?;
+        config.merge_into(&mut args, &matches);
+    }
+
+    Ok(args)
+}
+
+/// Settings loaded with `--config`. Unset fields keep the command line values (or defaults).
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScanConfig {
+    dns_seeds: Option<Vec<String>>,
+    network: Option<ScanNetwork>,
+    port: Option<u16>,
+    timeout: Option<u64>,
+    connect_timeout: Option<u64>,
+    version_timeout: Option<u64>,
+    verack_timeout: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_services")]
+    services: Option<ServiceIdentifier>,
+    protocol_version: Option<i32>,
+    user_agent: Option<String>,
+    max_per_family: Option<usize>,
+}
+
+/// Deserializes services from their names, like `--services`.
+fn deserialize_services<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<ServiceIdentifier>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl ScanConfig {
+    /// Overwrites the fields of `args` set in this config, except for the ones given on the command line.
+    fn merge_into(self, args: &mut Args, matches: &ArgMatches) {
+        let from_cli = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+        if let (Some(x), false) = (self.dns_seeds, from_cli("dns_seeds")) {
+            args.dns_seeds = x;
+        }
+        if let (Some(x), false) = (self.network, from_cli("network")) {
+            args.network = x;
+        }
+        if let (Some(x), false) = (self.port, from_cli("port")) {
+            args.port = Some(x);
+        }
+        if let (Some(x), false) = (self.timeout, from_cli("timeout")) {
+            args.timeout = x;
+        }
+        if let (Some(x), false) = (self.connect_timeout, from_cli("connect_timeout")) {
+            args.connect_timeout = x;
+        }
+        if let (Some(x), false) = (self.version_timeout, from_cli("version_timeout")) {
+            args.version_timeout = Some(x);
+        }
+        if let (Some(x), false) = (self.verack_timeout, from_cli("verack_timeout")) {
+            args.verack_timeout = Some(x);
+        }
+        if let (Some(x), false) = (self.services, from_cli("services")) {
+            args.services = x;
+        }
+        if let (Some(x), false) = (self.protocol_version, from_cli("protocol_version")) {
+            args.protocol_version = x;
+        }
+        if let (Some(x), false) = (self.user_agent, from_cli("user_agent")) {
+            args.user_agent = x;
+        }
+        if let (Some(x), false) = (self.max_per_family, from_cli("max_per_family")) {
+            args.max_per_family = Some(x);
+        }
+    }
+}
+
+#[instrument(name = "handshake", skip(args, pool))]
+async fn process(
+    target: SocketAddr,
+    args: &Args,
+    pool: &BufferPool,
+) -> Result<MessageExchangeResult> {
+    let result = match connect(target, args).await {
+        Ok(stream) => {
+            let phases = PhaseLog::new();
+            timeout(
+                Duration::from_secs(args.timeout),
+                process_inner(stream, args, pool, &phases),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(eyre!(
+                    "timed out waiting for {} after {}s",
+                    phases.awaiting(),
+                    phases.elapsed().as_secs()
+                ))
+            })
+        }
+        Err(e) => Err(e),
     };
 
     match result {
-        Ok(MessageExchangeResult::Ok) => tracing::info!("handshake succeeded"),
+        Ok(MessageExchangeResult::Ok { ref user_agent }) => {
+            tracing::info!("handshake succeeded with `{}`", user_agent)
+        }
         Ok(MessageExchangeResult::PartialOk) => {
             tracing::info!("handshake *partially* succeeded")
         }
+        Ok(MessageExchangeResult::Probed {
+            ref user_agent,
+            version,
+            services,
+            start_height,
+        }) => tracing::info!(
+            "probed `{}`: version `{}`, services `{}`, start height `{}`",
+            user_agent,
+            version,
+            services,
+            start_height
+        ),
+        Ok(MessageExchangeResult::Rejected(ref reject)) => {
+            tracing::warn!("peer rejected us: {}", reject.to_string().escape_debug())
+        }
+        Ok(MessageExchangeResult::Misbehaved { disconnected: true }) => {
+            tracing::info!("peer disconnected after our protocol violation")
+        }
+        Ok(MessageExchangeResult::Misbehaved {
+            disconnected: false,
+        }) => tracing::info!("peer tolerated our protocol violation"),
         Err(ref e) => tracing::error!("handshake attempt failed with: {}", e),
     };
+    if args.json_lines {
+        if let Err(e) = print_json_line(target, &result) {
+            tracing::error!("failed to print result: {}", e);
+        }
+    }
 
     result
 }
 
-async fn process_inner(target: SocketAddr) -> Result<MessageExchangeResult> {
+/// Prints the result of the handshake with `target` as a single line JSON object, and flushes stdout.
+fn print_json_line(target: SocketAddr, result: &Result<MessageExchangeResult>) -> Result<()> {
+    let line = match result {
+        Ok(MessageExchangeResult::Ok { .. }) => format!(r#"{{"addr":"{}","result":"ok"}}"#, target),
+        Ok(MessageExchangeResult::PartialOk) => {
+            format!(r#"{{"addr":"{}","result":"partial_ok"}}"#, target)
+        }
+        Ok(MessageExchangeResult::Probed {
+            user_agent,
+            version,
+            services,
+            start_height,
+        }) => format!(
+            r#"{{"addr":"{}","result":"probed","user_agent":"{}","version":{},"services":{},"start_height":{}}}"#,
+            target,
+            json_escape(user_agent),
+            version,
+            services.bits(),
+            start_height
+        ),
+        Ok(MessageExchangeResult::Misbehaved { disconnected }) => format!(
+            r#"{{"addr":"{}","result":"{}"}}"#,
+            target,
+            if *disconnected {
+                "disconnected"
+            } else {
+                "tolerated"
+            }
+        ),
+        Ok(MessageExchangeResult::Rejected(reject)) => format!(
+            r#"{{"addr":"{}","result":"rejected","ccode":"{}","reason":"{}"}}"#,
+            target,
+            reject.ccode(),
+            json_escape(reject.reason())
+        ),
+        Err(e) => format!(
+            r#"{{"addr":"{}","result":"failed","error":"{}"}}"#,
+            target,
+            json_escape(&e.to_string())
+        ),
+    };
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", line)?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Escapes `s` for use in a JSON string.
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Opens the TCP connection to `target`, within `--connect-timeout`.
+async fn connect(target: SocketAddr, args: &Args) -> Result<TcpStream> {
+    let stream = timeout(
+        Duration::from_secs(args.connect_timeout),
+        TcpStream::connect(target),
+    )
+    .await
+    .map_err(|_| eyre!("TCP connect timed out after {}s", args.connect_timeout))??;
+
+    Ok(stream)
+}
+
+/// Handshake phase, as the handshake progresses. Logged with [`PhaseLog`], so the last logged phase of a hanging
+/// handshake shows where it stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Connected,
+    VersionSent,
+    VersionReceived,
+    VerackSent,
+    VerackReceived,
+}
+
+impl Phase {
+    /// What the handshake waits for in this phase.
+    fn awaiting(self) -> &'static str {
+        match self {
+            Phase::Connected => "sending `version`",
+            Phase::VersionSent => "`version`",
+            Phase::VersionReceived => "sending `verack`",
+            Phase::VerackSent => "`verack`",
+            Phase::VerackReceived => "disconnect",
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Connected => "connected",
+            Phase::VersionSent => "version-sent",
+            Phase::VersionReceived => "version-received",
+            Phase::VerackSent => "verack-sent",
+            Phase::VerackReceived => "verack-received",
+        })
+    }
+}
+
+/// Tracks the current [`Phase`] of a handshake, logging each transition with the time elapsed since connecting.
+struct PhaseLog {
+    connected_at: Instant,
+    phase: Cell<Phase>,
+}
+
+impl PhaseLog {
+    fn new() -> Self {
+        Self {
+            connected_at: Instant::now(),
+            phase: Cell::new(Phase::Connected),
+        }
+    }
+
+    fn enter(&self, phase: Phase) {
+        self.phase.set(phase);
+        tracing::debug!("phase `{}` after {:?}", phase, self.elapsed());
+    }
+
+    fn awaiting(&self) -> &'static str {
+        self.phase.get().awaiting()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.connected_at.elapsed()
+    }
+}
+
+async fn process_inner(
+    stream: TcpStream,
+    args: &Args,
+    pool: &BufferPool,
+    phases: &PhaseLog,
+) -> Result<MessageExchangeResult> {
+    if let Some(misbehavior) = args.misbehave {
+        return misbehave(stream, misbehavior, args, pool).await;
+    }
     tracing::debug!("Starting handshake");
-    let mut stream = TcpStream::connect(target).await?;
+    let target = stream.peer_addr()?;
+    let mut peer = Peer::with_pool(
+        RawDump::new(stream, args.dump_raw),
+        args.network(),
+        ReaderConfig::handshake(),
+        pool,
+    );
 
     // send & expect Version
-    let version_data = VersionData::new(
-        ServiceIdentifier::NODE_NETWORK,
+    let now = i64::try_from(
         SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)?
-            .as_secs() as i64,
-        ServiceIdentifier::NODE_NETWORK,
-        stream.local_addr()?,
-        ServiceIdentifier::NODE_NETWORK,
-        target,
-        "".to_string(),
-        0,
-        false,
-    );
-    let payload = Payload::Version(version_data);
-    let version = Message::new(START_STRING_MAINNET, Command::Version, payload);
-    match send_and_expect(&mut stream, &version).await {
-        Ok(MessageExchangeResult::Ok) => {}
-        Ok(MessageExchangeResult::PartialOk) => {
-            return Err(eyre!("Partial OK on `version` exchange is an error"))
+            .as_secs(),
+    )?;
+    let version_data =
+        outbound_version(args, target, rand::random(), now).checked_timestamp(now)?;
+    peer.send_version(version_data).await?;
+    phases.enter(Phase::VersionSent);
+    let version_data = match peer
+        .recv_version_timeout(step_timeout(args.version_timeout, args.timeout))
+        .await
+    {
+        Ok(v) => v,
+        Err(BitcoinMessageError::Rejected(reject)) => {
+            return Ok(MessageExchangeResult::Rejected(reject))
         }
-        Err(e) => return Err(e),
+        Err(e) => {
+            if let Some(c) = e.unexpected_command() {
+                tracing::warn!("expected message command `version` but got `{}` instead", c);
+                return Err(eyre!("Partial OK on `version` exchange is an error"));
+            }
+            return Err(handshake_error(e));
+        }
+    };
+    phases.enter(Phase::VersionReceived);
+    if *version_data.version() != PROTOCOL_VERSION {
+        tracing::warn!(
+            "received message version`{}`, while this tool implements `{}`",
+            version_data.version(),
+            PROTOCOL_VERSION
+        );
+    }
+    if let Some(v) = peer.negotiated_version() {
+        tracing::debug!("negotiated protocol version `{}`", v);
+    }
+    if args.probe_only {
+        peer.disconnect().await?;
+        return Ok(MessageExchangeResult::Probed {
+            user_agent: version_data.user_agent_sanitized(),
+            version: *version_data.version(),
+            services: *version_data.services(),
+            start_height: *version_data.start_height(),
+        });
     }
 
     // send & expect VerAck
-    let verack = Message::new(START_STRING_MAINNET, Command::VerAck, Payload::Empty);
-    send_and_expect(&mut stream, &verack).await
+    peer.send_verack().await?;
+    phases.enter(Phase::VerackSent);
+    match peer
+        .recv_verack_timeout(step_timeout(args.verack_timeout, args.timeout))
+        .await
+    {
+        Ok(()) => {
+            phases.enter(Phase::VerackReceived);
+            Ok(MessageExchangeResult::Ok {
+                user_agent: version_data.user_agent_sanitized(),
+            })
+        }
+        Err(BitcoinMessageError::Rejected(reject)) => Ok(MessageExchangeResult::Rejected(reject)),
+        Err(e) => match e.unexpected_command() {
+            Some(c) => {
+                tracing::warn!("expected message command `verack` but got `{}` instead", c);
+                Ok(MessageExchangeResult::PartialOk)
+            }
+            None => Err(handshake_error(e)),
+        },
+    }
+}
+
+/// Commits `misbehavior` on `stream`, then waits up to the `verack` step timeout for the peer to disconnect.
+async fn misbehave(
+    stream: TcpStream,
+    misbehavior: Misbehavior,
+    args: &Args,
+    pool: &BufferPool,
+) -> Result<MessageExchangeResult> {
+    let mut stream = RawDump::new(stream, args.dump_raw);
+    match misbehavior {
+        Misbehavior::VerackFirst => {
+            tracing::debug!("Sending `verack` without `version`");
+            send_bare_verack(&mut stream, args.network()).await?;
+        }
+    }
+    let mut stream = MessageStream::with_pool(stream, ReaderConfig::handshake(), pool);
+
+    let wait = step_timeout(args.verack_timeout, args.timeout);
+    let disconnected = timeout(wait, async {
+        loop {
+            match stream.next_message().await {
+                Ok(Some(m)) => tracing::debug!("peer sent `{}` meanwhile", m.command_str()),
+                Ok(None) | Err(BitcoinMessageError::SerializationError(_)) => return,
+                Err(e) => tracing::debug!("peer sent an invalid message meanwhile: {}", e),
+            }
+        }
+    })
+    .await
+    .is_ok();
+
+    Ok(MessageExchangeResult::Misbehaved { disconnected })
+}
+
+/// Builds the `version` sent to `target`.
+fn outbound_version(args: &Args, target: SocketAddr, nonce: u64, timestamp: i64) -> VersionData {
+    VersionData::with_nonce(
+        nonce,
+        args.services,
+        timestamp,
+        args.services,
+        target,
+        args.services,
+        // don't leak the (possibly private) local address
+        UNSPECIFIED_SOCKET_ADDRESS,
+        args.user_agent.clone(),
+        0,
+        false,
+    )
+    .with_advertised_version(args.protocol_version)
+}
+
+/// Returns the timeout of a single handshake round-trip: `step` if given, or half of `total` (both in seconds).
+fn step_timeout(step: Option<u64>, total: u64) -> Duration {
+    step.map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(total) / 2)
 }
 
 enum MessageExchangeResult {
-    Ok,
+    /// The peer's user agent, sanitized for logging.
+    Ok {
+        user_agent: String,
+    },
     PartialOk,
+    /// Only `version` was exchanged (`--probe-only`), with what the peer advertised in it.
+    Probed {
+        user_agent: String,
+        version: i32,
+        services: ServiceIdentifier,
+        start_height: i32,
+    },
+    /// The peer answered our `version` with a `reject`.
+    Rejected(RejectData),
+    /// We violated the protocol (`--misbehave`), and the peer either disconnected within the timeout, or didn't.
+    Misbehaved {
+        disconnected: bool,
+    },
 }
 
-async fn send_and_expect(
-    stream: &mut (impl AsyncWrite + AsyncRead + Unpin),
-    message: &Message,
-) -> Result<MessageExchangeResult> {
-    // send
-    let nonce = match message.payload() {
-        Payload::Empty => None,
-        Payload::Version(d) => Some(d.nonce()),
-    };
-    let bytes = message.to_bytes()?;
-    tracing::trace!("TX {:#?}", message);
-    stream.write_all(&bytes).await?;
-    tracing::debug!("Sent {} bytes", bytes.len());
-
-    // read data from IO
-    let mut br = BufReader::new(stream);
-    let mut rx = br.fill_buf().await?;
-    let n_recv = rx.len();
-    tracing::debug!("Received {} bytes", n_recv);
-
-    // deserialize message
-    let msg_recv = match Message::from_bytes(&mut rx) {
-        Ok(m) => m,
-        Err(bitcoin_handshake::errors::BitcoinMessageError::CommandNameUnknown(m)) => {
-            tracing::warn!(
-                "expected message command `{}` but got `{}` instead",
-                message.command(),
-                m
-            );
-            br.consume(n_recv);
-            return Ok(MessageExchangeResult::PartialOk);
-        }
-        Err(e) => return Err(e.into()),
-    };
-    tracing::trace!("RX {:#?}", msg_recv);
-
-    // check for nonce conflict
-    if let Some(n) = nonce {
-        if let Payload::Version(version_data) = msg_recv.payload() {
-            if version_data.nonce() == n {
-                br.consume(n_recv);
-                return Err(eyre!("nonce conflict"));
-            }
+fn handshake_error(e: BitcoinMessageError) -> color_eyre::Report {
+    match e {
+        BitcoinMessageError::ConnectionClosed => eyre!("peer closed connection"),
+        BitcoinMessageError::NonceConflict => eyre!("nonce conflict"),
+        e => e.into(),
+    }
+}
+
+/// Stream wrapper hex-printing all sent and received bytes, when enabled.
+struct RawDump<T> {
+    inner: T,
+    enabled: bool,
+}
+
+impl<T> RawDump<T> {
+    fn new(inner: T, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for RawDump<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if self.enabled && buf.filled().len() > filled {
+            tracing::info!("RX {}", to_hex(&buf.filled()[filled..]));
         }
+
+        result
     }
+}
 
-    // check for version match
-    if let Payload::Version(version_data) = msg_recv.payload() {
-        if *version_data.version() != PROTOCOL_VERSION {
-            tracing::warn!(
-                "received message version`{}`, while this tool implements `{}`",
-                version_data.version(),
-                PROTOCOL_VERSION
-            );
+impl<T: AsyncWrite + Unpin> AsyncWrite for RawDump<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let (true, Poll::Ready(Ok(n))) = (self.enabled, &result) {
+            tracing::info!("TX {}", to_hex(&buf[..*n]));
         }
+
+        result
     }
 
-    // check for expected command
-    if msg_recv.command() != message.command() {
-        tracing::warn!(
-            "expected message command `{}` but got `{}` instead",
-            message.command(),
-            msg_recv.command()
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    /// What `process_inner` sends with default arguments, to `203.0.113.5:8333`.
+    const OUTBOUND_VERSION: &str = concat!(
+        // header
+        "f9beb4d976657273696f6e000000000056000000800b0f34",
+        // version, services, timestamp
+        "7f110100",
+        "0100000000000000",
+        "00f1536500000000",
+        // addr_recv
+        "0100000000000000",
+        "00000000000000000000ffffcb007105",
+        "208d",
+        // addr_trans
+        "0100000000000000",
+        "00000000000000000000000000000000",
+        "0000",
+        // nonce, user_agent, start_height, relay
+        "0807060504030201",
+        "00",
+        "00000000",
+        "00",
+    );
+
+    #[test]
+    fn scan_config_parses() {
+        let config: ScanConfig = toml::from_str(
+            r#"
+            # signet scan
+            dns_seeds = ["seed.signet.bitcoin.sprovoost.nl", 'seed.signet.achownodes.xyz']
+            network = "signet"
+            port = 38_333 # non-default ports work too
+            timeout = 20
+            connect_timeout = 2
+            verack_timeout = 5
+            services = "NODE_NETWORK|NODE_WITNESS"
+            protocol_version = 70016
+            user_agent = "/scanner:\u0031.0/"
+            max_per_family = 50
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            ScanConfig {
+                dns_seeds: Some(vec![
+                    "seed.signet.bitcoin.sprovoost.nl".to_string(),
+                    "seed.signet.achownodes.xyz".to_string()
+                ]),
+                network: Some(ScanNetwork::Signet),
+                port: Some(38333),
+                timeout: Some(20),
+                connect_timeout: Some(2),
+                version_timeout: None,
+                verack_timeout: Some(5),
+                services: Some(ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS),
+                protocol_version: Some(70016),
+                user_agent: Some("/scanner:1.0/".to_string()),
+                max_per_family: Some(50),
+            }
         );
-        br.consume(n_recv);
-        return Ok(MessageExchangeResult::PartialOk);
+        assert!(toml::from_str::<ScanConfig>("timeout = \"20\"").is_err());
+        assert!(toml::from_str::<ScanConfig>("retries = 3").is_err());
+        assert!(toml::from_str::<ScanConfig>("services = \"NODE_FOO\"").is_err());
+    }
+
+    #[test]
+    fn outbound_version_snapshot() {
+        let matches = Args::command().get_matches_from(["bitcoin-handshake"]);
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let target = SocketAddr::from(([203, 0, 113, 5], 8333));
+        let version = outbound_version(&args, target, 0x0102030405060708, 1_700_000_000);
+
+        let bytes = Message::new(
+            Network::Mainnet.magic(),
+            Command::Version,
+            Payload::Version(version),
+        )
+        .to_bytes()
+        .unwrap();
+
+        assert_eq!(to_hex(&bytes), OUTBOUND_VERSION);
+    }
+
+    #[test]
+    fn dedup_addrs_keeps_first_appearance() {
+        let a = SocketAddr::from(([10, 0, 0, 1], 8333));
+        let b = SocketAddr::from(([10, 0, 0, 2], 8333));
+
+        assert_eq!(dedup_addrs([a, b, a, b, a].into_iter()), vec![a, b]);
     }
 
-    br.consume(n_recv);
+    #[tokio::test]
+    async fn until_successes_cancels_the_rest() {
+        let target = SocketAddr::from(([10, 0, 0, 1], 8333));
+        let handshakes = (0..4).map(|i| async move {
+            let result = match i {
+                0 => Err(eyre!("failed")),
+                1 | 2 => Ok(MessageExchangeResult::Ok {
+                    user_agent: String::new(),
+                }),
+                _ => futures::future::pending().await,
+            };
+            Attempt {
+                target,
+                result,
+                elapsed: Duration::ZERO,
+            }
+        });
+
+        let results = until_successes(handshakes, 2).await;
 
-    Ok(MessageExchangeResult::Ok)
+        assert_eq!(results.len(), 3);
+        assert_eq!(results.iter().filter(|a| a.result.is_ok()).count(), 2);
+    }
+
+    #[test]
+    fn cidr_filter_skips_denied() {
+        let cidr = |s: &str| s.parse::<Cidr>().unwrap();
+        let allow = [cidr("10.0.0.0/8"), cidr("2001:db8::/32")];
+        let deny = [cidr("10.1.0.0/16")];
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert!(is_allowed(ip("10.2.3.4"), &allow, &deny));
+        assert!(is_allowed(ip("::ffff:10.2.3.4"), &allow, &deny));
+        assert!(is_allowed(ip("2001:db8::1"), &allow, &deny));
+        assert!(!is_allowed(ip("10.1.2.3"), &allow, &deny));
+        assert!(!is_allowed(ip("192.168.0.1"), &allow, &deny));
+        assert!(is_allowed(ip("192.168.0.1"), &[], &deny));
+        assert!(!is_allowed(ip("10.1.2.3"), &[], &deny));
+        assert!(is_allowed(ip("1.2.3.4"), &[cidr("0.0.0.0/0")], &[]));
+        assert!(is_allowed(
+            ip("10.2.3.4"),
+            &[cidr("::ffff:10.0.0.0/104")],
+            &[]
+        ));
+        assert!(!is_allowed(
+            ip("11.2.3.4"),
+            &[cidr("::ffff:10.0.0.0/104")],
+            &[]
+        ));
+        assert!(!is_allowed(
+            ip("10.1.2.3"),
+            &[],
+            &[cidr("::ffff:10.1.0.0/112")]
+        ));
+        assert_eq!(cidr("::ffff:10.0.0.0/104"), cidr("10.0.0.0/8"));
+        assert!("::ffff:10.0.0.0/95".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0.0".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let sorted: Vec<_> = (1..=10).map(Duration::from_secs).collect();
+
+        assert_eq!(percentile(&sorted, 50), Duration::from_secs(5));
+        assert_eq!(percentile(&sorted, 90), Duration::from_secs(9));
+        assert_eq!(percentile(&sorted, 99), Duration::from_secs(10));
+        assert_eq!(percentile(&sorted, 0), Duration::from_secs(1));
+        assert_eq!(percentile(&sorted[..1], 99), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn interleave_families_alternates() {
+        let v4 = |n| SocketAddr::from(([10, 0, 0, n], 8333));
+        let v6 = |n| SocketAddr::from(([0x2001, 0xdb8, 0, 0, 0, 0, 0, n], 8333));
+
+        assert_eq!(
+            interleave_families(vec![v6(1), v6(2), v6(3), v4(1), v6(4), v4(2)]),
+            vec![v4(1), v6(1), v4(2), v6(2), v6(3), v6(4)]
+        );
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let matches = Args::command().get_matches_from(["bitcoin-handshake", "--timeout", "3"]);
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let config = ScanConfig {
+            dns_seeds: Some(vec!["localhost".to_string()]),
+            network: Some(ScanNetwork::Regtest),
+            timeout: Some(20),
+            ..Default::default()
+        };
+
+        config.merge_into(&mut args, &matches);
+
+        assert_eq!(args.dns_seeds, vec!["localhost".to_string()]);
+        assert_eq!(args.network(), Network::Regtest);
+        assert_eq!(args.port(), Network::Regtest.default_port());
+        assert_eq!(args.timeout, 3);
+    }
 }
@@ -0,0 +1,715 @@
+use crate::{
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    message::{BitcoinDeserialize, BitcoinSerialize},
+    types::{read_var_bytes, var_bytes_len, write_var_bytes, Hash, VarInt, HASH_SIZE},
+    utils::double_sha256,
+};
+use alloc::vec::Vec;
+use byteorder::LittleEndian;
+#[cfg(feature = "getset")]
+use getset::Getters;
+
+/// Size of a serialized [`BlockHeader`], in bytes.
+pub const BLOCK_HEADER_SIZE: usize = 80;
+
+/// Header of a [`Block`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct BlockHeader {
+    /// The block version number indicates which set of block validation rules to follow.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    version: i32,
+
+    /// Hash of the previous block’s header.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    prev_block: Hash,
+
+    /// Merkle root of all the transactions in this block.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    merkle_root: Hash,
+
+    /// The block time is a Unix epoch time when the miner started hashing the header (according to the miner).
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    timestamp: u32,
+
+    /// An encoded version of the target threshold this block’s header hash must be less than or equal to.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    bits: u32,
+
+    /// An arbitrary number miners change to modify the header hash in order to produce a hash less than or equal to the target threshold.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    nonce: u32,
+}
+
+#[cfg(not(feature = "getset"))]
+impl BlockHeader {
+    /// The block version number indicates which set of block validation rules to follow.
+    #[inline(always)]
+    pub fn version(&self) -> &i32 {
+        &self.version
+    }
+
+    /// Hash of the previous block’s header.
+    #[inline(always)]
+    pub fn prev_block(&self) -> &Hash {
+        &self.prev_block
+    }
+
+    /// Merkle root of all the transactions in this block.
+    #[inline(always)]
+    pub fn merkle_root(&self) -> &Hash {
+        &self.merkle_root
+    }
+
+    /// The block time is a Unix epoch time when the miner started hashing the header (according to the miner).
+    #[inline(always)]
+    pub fn timestamp(&self) -> &u32 {
+        &self.timestamp
+    }
+
+    /// An encoded version of the target threshold this block’s header hash must be less than or equal to.
+    #[inline(always)]
+    pub fn bits(&self) -> &u32 {
+        &self.bits
+    }
+
+    /// An arbitrary number miners change to modify the header hash in order to produce a hash less than or equal to the target threshold.
+    #[inline(always)]
+    pub fn nonce(&self) -> &u32 {
+        &self.nonce
+    }
+}
+
+impl BlockHeader {
+    /// Creates new [`BlockHeader`].
+    pub fn new(
+        version: i32,
+        prev_block: Hash,
+        merkle_root: Hash,
+        timestamp: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        Self {
+            version,
+            prev_block,
+            merkle_root,
+            timestamp,
+            bits,
+            nonce,
+        }
+    }
+
+    /// Returns the hash of this header, identifying the block; the double-SHA256 of the serialized header.
+    ///
+    /// Each header refers to the previous one by this hash, in [`BlockHeader::prev_block`].
+    pub fn block_hash(&self) -> Hash {
+        Hash::new(double_sha256(&self.serialize()))
+    }
+
+    /// Serializes the header; unlike [`BitcoinSerialize::to_bytes`] this can't fail, as the size is fixed.
+    fn serialize(&self) -> [u8; BLOCK_HEADER_SIZE] {
+        let mut buf = [0u8; BLOCK_HEADER_SIZE];
+        buf[0..4].copy_from_slice(&self.version.to_le_bytes());
+        buf[4..36].copy_from_slice(self.prev_block.as_bytes());
+        buf[36..68].copy_from_slice(self.merkle_root.as_bytes());
+        buf[68..72].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        buf[76..80].copy_from_slice(&self.nonce.to_le_bytes());
+
+        buf
+    }
+}
+
+impl BitcoinSerialize for BlockHeader {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        Ok(self.serialize().to_vec())
+    }
+}
+
+impl BitcoinDeserialize for BlockHeader {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        Ok(Self {
+            version: data.read_i32::<LittleEndian>()?,
+            prev_block: Hash::from_bytes(data)?,
+            merkle_root: Hash::from_bytes(data)?,
+            timestamp: data.read_u32::<LittleEndian>()?,
+            bits: data.read_u32::<LittleEndian>()?,
+            nonce: data.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+/// Reference to a particular output of a previous transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct OutPoint {
+    /// The TXID of the transaction holding the output to spend.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    txid: Hash,
+
+    /// The output index number of the specific output to spend from the transaction.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    index: u32,
+}
+
+#[cfg(not(feature = "getset"))]
+impl OutPoint {
+    /// The TXID of the transaction holding the output to spend.
+    #[inline(always)]
+    pub fn txid(&self) -> &Hash {
+        &self.txid
+    }
+
+    /// The output index number of the specific output to spend from the transaction.
+    #[inline(always)]
+    pub fn index(&self) -> &u32 {
+        &self.index
+    }
+}
+
+impl OutPoint {
+    /// Creates new [`OutPoint`].
+    pub fn new(txid: Hash, index: u32) -> Self {
+        Self { txid, index }
+    }
+}
+
+/// Transaction input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct TxIn {
+    /// The previous output being spent.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    previous_output: OutPoint,
+
+    /// A script-language script which satisfies the conditions placed in the outpoint’s pubkey script.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    script_sig: Vec<u8>,
+
+    /// Sequence number.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    sequence: u32,
+
+    /// Witness stack items of this input (BIP144). Empty for non-segwit inputs.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    witness: Vec<Vec<u8>>,
+}
+
+#[cfg(not(feature = "getset"))]
+impl TxIn {
+    /// The previous output being spent.
+    #[inline(always)]
+    pub fn previous_output(&self) -> &OutPoint {
+        &self.previous_output
+    }
+
+    /// A script-language script which satisfies the conditions placed in the outpoint’s pubkey script.
+    #[inline(always)]
+    pub fn script_sig(&self) -> &Vec<u8> {
+        &self.script_sig
+    }
+
+    /// Sequence number.
+    #[inline(always)]
+    pub fn sequence(&self) -> &u32 {
+        &self.sequence
+    }
+
+    /// Witness stack items of this input (BIP144). Empty for non-segwit inputs.
+    #[inline(always)]
+    pub fn witness(&self) -> &Vec<Vec<u8>> {
+        &self.witness
+    }
+}
+
+impl TxIn {
+    /// Creates new [`TxIn`].
+    pub fn new(
+        previous_output: OutPoint,
+        script_sig: Vec<u8>,
+        sequence: u32,
+        witness: Vec<Vec<u8>>,
+    ) -> Self {
+        Self {
+            previous_output,
+            script_sig,
+            sequence,
+            witness,
+        }
+    }
+}
+
+/// Transaction output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct TxOut {
+    /// Number of satoshis to spend.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    value: i64,
+
+    /// Defines the conditions which must be satisfied to spend this output.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    script_pubkey: Vec<u8>,
+}
+
+#[cfg(not(feature = "getset"))]
+impl TxOut {
+    /// Number of satoshis to spend.
+    #[inline(always)]
+    pub fn value(&self) -> &i64 {
+        &self.value
+    }
+
+    /// Defines the conditions which must be satisfied to spend this output.
+    #[inline(always)]
+    pub fn script_pubkey(&self) -> &Vec<u8> {
+        &self.script_pubkey
+    }
+}
+
+impl TxOut {
+    /// Creates new [`TxOut`].
+    pub fn new(value: i64, script_pubkey: Vec<u8>) -> Self {
+        Self {
+            value,
+            script_pubkey,
+        }
+    }
+}
+
+/// Bitcoin transaction, in either the legacy or the segwit (BIP144) serialization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct Transaction {
+    /// Transaction version number.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    version: i32,
+
+    /// Transaction inputs.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    inputs: Vec<TxIn>,
+
+    /// Transaction outputs.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    outputs: Vec<TxOut>,
+
+    /// A time (Unix epoch time) or block number.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    lock_time: u32,
+}
+
+#[cfg(not(feature = "getset"))]
+impl Transaction {
+    /// Transaction version number.
+    #[inline(always)]
+    pub fn version(&self) -> &i32 {
+        &self.version
+    }
+
+    /// Transaction inputs.
+    #[inline(always)]
+    pub fn inputs(&self) -> &Vec<TxIn> {
+        &self.inputs
+    }
+
+    /// Transaction outputs.
+    #[inline(always)]
+    pub fn outputs(&self) -> &Vec<TxOut> {
+        &self.outputs
+    }
+
+    /// A time (Unix epoch time) or block number.
+    #[inline(always)]
+    pub fn lock_time(&self) -> &u32 {
+        &self.lock_time
+    }
+}
+
+impl Transaction {
+    /// Creates new [`Transaction`].
+    pub fn new(version: i32, inputs: Vec<TxIn>, outputs: Vec<TxOut>, lock_time: u32) -> Self {
+        Self {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        }
+    }
+
+    /// Returns `true` if any input carries witness data, i.e. the transaction uses the segwit serialization.
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|i| !i.witness.is_empty())
+    }
+
+    /// Returns the length of the serialized [`Transaction`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        let has_witness = self.has_witness();
+        let mut len = 4 + VarInt::from(self.inputs.len()).len();
+        for input in &self.inputs {
+            len += HASH_SIZE + 4 + var_bytes_len(&input.script_sig) + 4;
+            if has_witness {
+                len += VarInt::from(input.witness.len()).len();
+                len += input
+                    .witness
+                    .iter()
+                    .map(|w| var_bytes_len(w))
+                    .sum::<usize>();
+            }
+        }
+        len += VarInt::from(self.outputs.len()).len();
+        for output in &self.outputs {
+            len += 8 + var_bytes_len(&output.script_pubkey);
+        }
+        if has_witness {
+            // marker & flag
+            len += 2;
+        }
+
+        len + 4
+    }
+}
+
+impl BitcoinSerialize for Transaction {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::new();
+        buf.write_i32::<LittleEndian>(self.version)?;
+        let has_witness = self.has_witness();
+        if has_witness {
+            // marker & flag
+            buf.write_all(&[0x00, 0x01])?;
+        }
+        buf.write_all(&VarInt::from(self.inputs.len()).to_bytes()?)?;
+        for input in &self.inputs {
+            buf.write_all(input.previous_output.txid.as_bytes())?;
+            buf.write_u32::<LittleEndian>(input.previous_output.index)?;
+            write_var_bytes(&mut buf, &input.script_sig)?;
+            buf.write_u32::<LittleEndian>(input.sequence)?;
+        }
+        buf.write_all(&VarInt::from(self.outputs.len()).to_bytes()?)?;
+        for output in &self.outputs {
+            buf.write_i64::<LittleEndian>(output.value)?;
+            write_var_bytes(&mut buf, &output.script_pubkey)?;
+        }
+        if has_witness {
+            for input in &self.inputs {
+                buf.write_all(&VarInt::from(input.witness.len()).to_bytes()?)?;
+                for item in &input.witness {
+                    write_var_bytes(&mut buf, item)?;
+                }
+            }
+        }
+        buf.write_u32::<LittleEndian>(self.lock_time)?;
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for Transaction {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let version = data.read_i32::<LittleEndian>()?;
+        let mut input_count = VarInt::from_bytes(data)?.0;
+        // an empty input list is the segwit marker, followed by the flag
+        let has_witness = input_count == 0;
+        if has_witness {
+            match data.read_u8()? {
+                0x01 => {}
+                flag => return Err(BitcoinMessageError::InvalidSegwitFlag(flag)),
+            }
+            input_count = VarInt::from_bytes(data)?.0;
+        }
+        let mut inputs = Vec::new();
+        for i in 0..input_count {
+            let input = read_input(data).map_err(|e| e.in_field(format_args!("input[{}]", i)))?;
+            inputs.push(input);
+        }
+        let output_count = VarInt::from_bytes(data)?.0;
+        let mut outputs = Vec::new();
+        for i in 0..output_count {
+            let output =
+                read_output(data).map_err(|e| e.in_field(format_args!("output[{}]", i)))?;
+            outputs.push(output);
+        }
+        if has_witness {
+            for (i, input) in inputs.iter_mut().enumerate() {
+                input.witness = read_witness(data)
+                    .map_err(|e| e.in_field(format_args!("input[{}].witness", i)))?;
+            }
+        }
+        let lock_time = data.read_u32::<LittleEndian>()?;
+
+        Ok(Self {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        })
+    }
+}
+
+fn read_input(data: &mut impl Read) -> Result<TxIn, BitcoinMessageError> {
+    let txid = Hash::from_bytes(data)?;
+    let index = data.read_u32::<LittleEndian>()?;
+    let script_sig = read_var_bytes(data).map_err(|e| e.in_field("script"))?;
+    let sequence = data.read_u32::<LittleEndian>()?;
+
+    Ok(TxIn::new(
+        OutPoint::new(txid, index),
+        script_sig,
+        sequence,
+        Vec::new(),
+    ))
+}
+
+fn read_output(data: &mut impl Read) -> Result<TxOut, BitcoinMessageError> {
+    let value = data.read_i64::<LittleEndian>()?;
+    let script_pubkey = read_var_bytes(data).map_err(|e| e.in_field("script"))?;
+
+    Ok(TxOut::new(value, script_pubkey))
+}
+
+fn read_witness(data: &mut impl Read) -> Result<Vec<Vec<u8>>, BitcoinMessageError> {
+    let item_count = VarInt::from_bytes(data)?.0;
+    (0..item_count).map(|_| read_var_bytes(data)).collect()
+}
+
+/// `block` message payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct Block {
+    /// The block header.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    header: BlockHeader,
+
+    /// Transactions included in this block.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    transactions: Vec<Transaction>,
+}
+
+#[cfg(not(feature = "getset"))]
+impl Block {
+    /// The block header.
+    #[inline(always)]
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Transactions included in this block.
+    #[inline(always)]
+    pub fn transactions(&self) -> &Vec<Transaction> {
+        &self.transactions
+    }
+}
+
+impl Block {
+    /// Creates new [`Block`].
+    pub fn new(header: BlockHeader, transactions: Vec<Transaction>) -> Self {
+        Self {
+            header,
+            transactions,
+        }
+    }
+
+    /// Returns the length of the serialized [`Block`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        BLOCK_HEADER_SIZE
+            + VarInt::from(self.transactions.len()).len()
+            + self
+                .transactions
+                .iter()
+                .map(Transaction::serialized_len)
+                .sum::<usize>()
+    }
+}
+
+impl BitcoinSerialize for Block {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = self.header.to_bytes()?;
+        buf.write_all(&VarInt::from(self.transactions.len()).to_bytes()?)?;
+        for tx in &self.transactions {
+            buf.write_all(&tx.to_bytes()?)?;
+        }
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for Block {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let header = BlockHeader::from_bytes(data)?;
+        let tx_count = VarInt::from_bytes(data)?.0;
+        let mut transactions = Vec::new();
+        for i in 0..tx_count {
+            let tx = Transaction::from_bytes(data)
+                .map_err(|e| e.in_field(format_args!("transaction[{}]", i)))?;
+            transactions.push(tx);
+        }
+
+        Ok(Self {
+            header,
+            transactions,
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use hex_literal::hex;
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    /// The mainnet genesis block.
+    pub(crate) const GENESIS_BLOCK: [u8; 285] = hex!(
+        "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000"
+    );
+
+    impl Arbitrary for BlockHeader {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            Self::new(
+                i32::arbitrary(g),
+                Hash::arbitrary(g),
+                Hash::arbitrary(g),
+                u32::arbitrary(g),
+                u32::arbitrary(g),
+                u32::arbitrary(g),
+            )
+        }
+    }
+
+    impl Arbitrary for Transaction {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let segwit = bool::arbitrary(g);
+            // the segwit serialization can't represent a transaction with no inputs
+            let input_count = usize::arbitrary(g) % 3 + 1;
+            let inputs = (0..input_count)
+                .map(|_| {
+                    let witness = if segwit {
+                        vec![Vec::<u8>::arbitrary(g), vec![0x01]]
+                    } else {
+                        vec![]
+                    };
+                    TxIn::new(
+                        OutPoint::new(Hash::arbitrary(g), u32::arbitrary(g)),
+                        Vec::arbitrary(g),
+                        u32::arbitrary(g),
+                        witness,
+                    )
+                })
+                .collect();
+            let outputs = (0..usize::arbitrary(g) % 3)
+                .map(|_| TxOut::new(i64::arbitrary(g), Vec::arbitrary(g)))
+                .collect();
+
+            Self::new(i32::arbitrary(g), inputs, outputs, u32::arbitrary(g))
+        }
+    }
+
+    impl Arbitrary for Block {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let transactions = (0..usize::arbitrary(g) % 3)
+                .map(|_| Transaction::arbitrary(g))
+                .collect();
+
+            Self::new(BlockHeader::arbitrary(g), transactions)
+        }
+    }
+
+    #[quickcheck]
+    fn block_header_roundtrip(x: BlockHeader) -> bool {
+        let bytes = x.to_bytes().unwrap();
+
+        bytes.len() == BLOCK_HEADER_SIZE
+            && BlockHeader::from_bytes(&mut bytes.as_slice()).unwrap() == x
+    }
+
+    #[quickcheck]
+    fn transaction_roundtrip(x: Transaction) -> bool {
+        let bytes = x.to_bytes().unwrap();
+
+        Transaction::from_bytes(&mut bytes.as_slice()).unwrap() == x
+    }
+
+    #[quickcheck]
+    fn block_roundtrip(x: Block) -> bool {
+        let bytes = x.to_bytes().unwrap();
+
+        Block::from_bytes(&mut bytes.as_slice()).unwrap() == x
+    }
+
+    #[test]
+    fn nested_error_has_field_path() {
+        let tx = |script_sig: Vec<u8>| {
+            let input =
+                |script_sig| TxIn::new(OutPoint::new(Hash::default(), 0), script_sig, 0, vec![]);
+            Transaction::new(1, vec![input(vec![0x51]), input(script_sig)], vec![], 0)
+        };
+        let marker = vec![0xde, 0xad, 0xbe, 0xef];
+        let block = Block::new(
+            BlockHeader::new(0, Hash::default(), Hash::default(), 0, 0, 0),
+            vec![tx(vec![0x51]), tx(vec![0x51]), tx(marker.clone())],
+        );
+        let bytes = block.to_bytes().unwrap();
+        let marker_at = bytes.windows(4).position(|w| w == marker).unwrap();
+
+        // cut the marker script short
+        let result = Block::from_bytes(&mut &bytes[..marker_at + 2]);
+
+        let Err(BitcoinMessageError::NestedField { context, source }) = result else {
+            panic!("expected nested field error, got {:?}", result);
+        };
+        assert_eq!(context, "transaction[2].input[1].script");
+        assert!(matches!(
+            *source,
+            BitcoinMessageError::SerializationError(_)
+        ));
+    }
+
+    #[test]
+    fn genesis_block_deserialization() {
+        let block = Block::from_bytes(&mut GENESIS_BLOCK.as_slice()).unwrap();
+
+        assert_eq!(*block.header().timestamp(), 1231006505);
+        assert_eq!(*block.header().nonce(), 2083236893);
+        assert_eq!(block.transactions().len(), 1);
+        assert_eq!(*block.transactions()[0].outputs()[0].value(), 50_0000_0000);
+        assert_eq!(block.to_bytes().unwrap(), GENESIS_BLOCK);
+    }
+
+    #[test]
+    fn genesis_block_hash() {
+        let block = Block::from_bytes(&mut GENESIS_BLOCK.as_slice()).unwrap();
+
+        assert_eq!(
+            block.header().block_hash().to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+
+    #[test]
+    fn segwit_transaction_deserialization() {
+        // BIP143 native P2WPKH example (signed)
+        let data = hex!("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000");
+
+        let tx = Transaction::from_bytes(&mut data.as_slice()).unwrap();
+
+        assert!(tx.has_witness());
+        assert_eq!(tx.inputs().len(), 2);
+        assert!(tx.inputs()[0].witness().is_empty());
+        assert_eq!(tx.inputs()[1].witness().len(), 2);
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(tx.to_bytes().unwrap(), data);
+    }
+}
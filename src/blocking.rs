@@ -0,0 +1,59 @@
+use crate::{
+    enums::{Command, Network},
+    errors::BitcoinMessageError,
+    message::{Message, Payload, VersionData},
+};
+use std::io::{Read, Write};
+
+/// Performs the handshake over a blocking `stream`, e.g. a `std::net::TcpStream`, returning the `version` of the
+/// other side.
+///
+/// Sends `version`, receives the other side's `version`, then exchanges `verack`s. Like
+/// [`Peer::recv_verack`](crate::peer::Peer::recv_verack), feature negotiation messages received before `verack`
+/// are skipped. Fails with [`BitcoinMessageError::Rejected`] if the other side rejected `version`, with
+/// [`BitcoinMessageError::UnexpectedCommand`] if any other message is received, and with
+/// [`BitcoinMessageError::NonceConflict`] if the received nonce matches the one in `version`.
+pub fn handshake_sync<S: Read + Write>(
+    stream: &mut S,
+    network: Network,
+    version: VersionData,
+) -> Result<VersionData, BitcoinMessageError> {
+    let local_nonce = *version.nonce();
+    Message::new(network.magic(), Command::Version, Payload::Version(version)).write_to(stream)?;
+
+    let remote = Message::read_from(stream)?.into_remote_version(Some(local_nonce))?;
+
+    Message::new(network.magic(), Command::VerAck, Payload::Empty).write_to(stream)?;
+    loop {
+        let message = Message::read_from(stream)?;
+        if message.completes_handshake()? {
+            return Ok(remote);
+        }
+        tracing::debug!("skipping `{}` before `verack`", message.command());
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::message::tests::version_data;
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn handshake_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let remote = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            handshake_sync(&mut stream, Network::Regtest, version_data(2)).unwrap()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let version = handshake_sync(&mut stream, Network::Regtest, version_data(1)).unwrap();
+
+        assert_eq!(version, version_data(2));
+        assert_eq!(remote.join().unwrap(), version_data(1));
+    }
+}
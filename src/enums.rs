@@ -1,10 +1,25 @@
-use std::fmt::Display;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Display, str::FromStr};
 
-use crate::errors::BitcoinMessageError;
+use crate::{
+    addr::{MAX_ADDR_LEN, TIMESTAMPED_ADDRESS_SIZE},
+    block::BLOCK_HEADER_SIZE,
+    errors::BitcoinMessageError,
+    headers::MAX_HEADERS_LEN,
+    inventory::{INVENTORY_VECTOR_SIZE, MAX_INVENTORY_LEN},
+    message::{COMMAND_NAME_SIZE, MAX_SIZE, MAX_SMALL_PAYLOAD_SIZE},
+};
 use bitflags::bitflags;
 
 bitflags! {
     /// Service identifier flags. See [bitcoin docs](https://developer.bitcoin.org/reference/p2p_networking.html#version).
+    ///
+    /// Decoding uses [`ServiceIdentifier::from_bits_retain`], so the bits of services this crate doesn't know, e.g.
+    /// ones defined after its release, round-trip.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct ServiceIdentifier: u64 {
         /// This node is not a full node. It may not be able to provide any data except for the transactions it originates.
         const UNNAMED = 0x00;
@@ -29,31 +44,248 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl ServiceIdentifier {
+    /// Services of a typical, non-pruned full node.
+    pub const FULL_NODE: Self = Self::all_of(&[
+        Self::NODE_NETWORK,
+        Self::NODE_WITNESS,
+        Self::NODE_BLOOM,
+        Self::NODE_NETWORK_LIMITED,
+    ]);
+
+    /// Services of a typical pruned node, serving only recent blocks.
+    pub const PRUNED_NODE: Self = Self::all_of(&[Self::NODE_WITNESS, Self::NODE_NETWORK_LIMITED]);
+
+    /// Combines all of `flags`; usable in `const` context, e.g. for defining presets.
+    pub const fn all_of(flags: &[Self]) -> Self {
+        let mut result = Self::UNNAMED;
+        let mut i = 0;
+        while i < flags.len() {
+            result = result.union(flags[i]);
+            i += 1;
+        }
+
+        result
+    }
+
+    /// Returns only the services this crate knows about.
+    pub const fn known_bits(&self) -> Self {
+        self.intersection(Self::all())
+    }
+
+    /// Returns the bits of services this crate doesn't know about, e.g. ones defined after its release.
+    pub const fn unknown_bits(&self) -> u64 {
+        self.bits() & !Self::all().bits()
+    }
+
+    /// Returns `true` if all `required` services are offered. Anything satisfies [`ServiceIdentifier::UNNAMED`], as
+    /// it requires nothing.
+    pub const fn satisfies(&self, required: Self) -> bool {
+        self.contains(required)
+    }
+
+    /// Returns the `required` services which aren't offered, [`ServiceIdentifier::UNNAMED`] if there are none.
+    pub const fn missing(&self, required: Self) -> Self {
+        required.difference(*self)
+    }
+
+    /// Returns `true` for a pruned node: advertising [`ServiceIdentifier::NODE_NETWORK_LIMITED`], but not
+    /// [`ServiceIdentifier::NODE_NETWORK`], so it only serves the last 288 blocks.
+    pub const fn is_pruned(&self) -> bool {
+        self.contains(Self::NODE_NETWORK_LIMITED) && !self.contains(Self::NODE_NETWORK)
+    }
+
+    /// Returns `true` for a node serving the whole chain, i.e. advertising [`ServiceIdentifier::NODE_NETWORK`].
+    pub const fn is_full_archive(&self) -> bool {
+        self.contains(Self::NODE_NETWORK)
+    }
+}
+
+impl FromStr for ServiceIdentifier {
+    type Err = BitcoinMessageError;
+
+    /// Parses flag names separated by `|`, e.g. `NODE_NETWORK|NODE_WITNESS`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('|')
+            .map(|name| match name.trim() {
+                "UNNAMED" => Ok(Self::UNNAMED),
+                "NODE_NETWORK" => Ok(Self::NODE_NETWORK),
+                "NODE_GETUTXO" => Ok(Self::NODE_GETUTXO),
+                "NODE_BLOOM" => Ok(Self::NODE_BLOOM),
+                "NODE_WITNESS" => Ok(Self::NODE_WITNESS),
+                "NODE_XTHIN" => Ok(Self::NODE_XTHIN),
+                "NODE_NETWORK_LIMITED" => Ok(Self::NODE_NETWORK_LIMITED),
+                x => Err(BitcoinMessageError::ServiceIdentifierUnknown(x.to_string())),
+            })
+            .try_fold(Self::UNNAMED, |acc, flag| Ok(acc | flag?))
+    }
+}
+
+impl Display for ServiceIdentifier {
+    /// Writes flag names separated by ` | `, e.g. `NODE_NETWORK | NODE_WITNESS`, with unknown bits in hex.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("UNNAMED");
+        }
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Enum corresponding to the `command_name` from Message header.
+///
+/// New commands may be added in minor releases, so matches on it must include a wildcard arm.
+///
+/// Commands are ordered by declaration, e.g. for stable output from a `BTreeMap<Command, _>`. New commands are
+/// only ever appended, so the order of the existing ones doesn't change.
+#[non_exhaustive]
 pub enum Command {
     /// `version` command_name
     Version,
 
     /// `verack` command_name
     VerAck,
+
+    /// `inv` command_name
+    Inv,
+
+    /// `getdata` command_name
+    GetData,
+
+    /// `notfound` command_name
+    NotFound,
+
+    /// `block` command_name
+    Block,
+
+    /// `ping` command_name
+    Ping,
+
+    /// `pong` command_name
+    Pong,
+
+    /// `getcfcheckpt` command_name
+    GetCfCheckpt,
+
+    /// `cfcheckpt` command_name
+    CfCheckpt,
+
+    /// `getheaders` command_name
+    GetHeaders,
+
+    /// `headers` command_name
+    Headers,
+
+    /// `wtxidrelay` command_name
+    WtxidRelay,
+
+    /// `sendaddrv2` command_name
+    SendAddrV2,
+
+    /// `sendheaders` command_name
+    SendHeaders,
+
+    /// `sendcmpct` command_name
+    SendCmpct,
+
+    /// `feefilter` command_name
+    FeeFilter,
+
+    /// `addr` command_name
+    Addr,
+
+    /// `getaddr` command_name
+    GetAddr,
+
+    /// `reject` command_name
+    Reject,
 }
 
 impl Command {
     /// Converts [`Command`] into byte vector.
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.to_string().into_bytes()
+        self.as_str().as_bytes().to_vec()
     }
-}
 
-impl Display for Command {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
+    /// Returns the command name, as used on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
             Command::Version => "version",
             Command::VerAck => "verack",
-        };
+            Command::Inv => "inv",
+            Command::GetData => "getdata",
+            Command::NotFound => "notfound",
+            Command::Block => "block",
+            Command::Ping => "ping",
+            Command::Pong => "pong",
+            Command::GetCfCheckpt => "getcfcheckpt",
+            Command::CfCheckpt => "cfcheckpt",
+            Command::GetHeaders => "getheaders",
+            Command::Headers => "headers",
+            Command::WtxidRelay => "wtxidrelay",
+            Command::SendAddrV2 => "sendaddrv2",
+            Command::SendHeaders => "sendheaders",
+            Command::SendCmpct => "sendcmpct",
+            Command::FeeFilter => "feefilter",
+            Command::Addr => "addr",
+            Command::GetAddr => "getaddr",
+            Command::Reject => "reject",
+        }
+    }
 
-        write!(f, "{}", s)
+    /// Returns `true` for the commands sent without a payload, decoded as [`Payload::Empty`](crate::message::Payload::Empty).
+    pub fn has_empty_payload(&self) -> bool {
+        matches!(
+            self,
+            Command::VerAck
+                | Command::WtxidRelay
+                | Command::SendAddrV2
+                | Command::SendHeaders
+                | Command::GetAddr
+        )
+    }
+
+    /// Returns the largest plausible payload length of this command, in bytes. Longer payloads are rejected when
+    /// decoding the header, with [`BitcoinMessageError::PayloadSizeUnexpected`].
+    ///
+    /// Lists are capped at their maximum number of items (plus a 3 byte count), small messages at
+    /// [`MAX_SMALL_PAYLOAD_SIZE`], and the rest at [`MAX_SIZE`].
+    pub const fn max_payload_len(&self) -> usize {
+        match self {
+            Command::Inv | Command::GetData | Command::NotFound => {
+                3 + MAX_INVENTORY_LEN * INVENTORY_VECTOR_SIZE
+            }
+            // each header is followed by an always empty transaction count
+            Command::Headers => 3 + MAX_HEADERS_LEN * (BLOCK_HEADER_SIZE + 1),
+            Command::Addr => 3 + MAX_ADDR_LEN * TIMESTAMPED_ADDRESS_SIZE,
+            Command::Block | Command::GetHeaders | Command::CfCheckpt => MAX_SIZE,
+            Command::Version
+            | Command::VerAck
+            | Command::Ping
+            | Command::Pong
+            | Command::GetCfCheckpt
+            | Command::WtxidRelay
+            | Command::SendAddrV2
+            | Command::SendHeaders
+            | Command::SendCmpct
+            | Command::FeeFilter
+            | Command::GetAddr
+            | Command::Reject => MAX_SMALL_PAYLOAD_SIZE,
+        }
+    }
+
+    /// Returns `true` for the feature negotiation commands, sent between `version` and `verack`.
+    pub fn is_negotiation(&self) -> bool {
+        matches!(
+            self,
+            Command::WtxidRelay | Command::SendAddrV2 | Command::SendHeaders | Command::SendCmpct
+        )
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -64,11 +296,214 @@ impl TryFrom<&str> for Command {
         match value {
             "version" => Ok(Command::Version),
             "verack" => Ok(Command::VerAck),
+            "inv" => Ok(Command::Inv),
+            "getdata" => Ok(Command::GetData),
+            "notfound" => Ok(Command::NotFound),
+            "block" => Ok(Command::Block),
+            "ping" => Ok(Command::Ping),
+            "pong" => Ok(Command::Pong),
+            "getcfcheckpt" => Ok(Command::GetCfCheckpt),
+            "cfcheckpt" => Ok(Command::CfCheckpt),
+            "getheaders" => Ok(Command::GetHeaders),
+            "headers" => Ok(Command::Headers),
+            "wtxidrelay" => Ok(Command::WtxidRelay),
+            "sendaddrv2" => Ok(Command::SendAddrV2),
+            "sendheaders" => Ok(Command::SendHeaders),
+            "sendcmpct" => Ok(Command::SendCmpct),
+            "feefilter" => Ok(Command::FeeFilter),
+            "addr" => Ok(Command::Addr),
+            "getaddr" => Ok(Command::GetAddr),
+            "reject" => Ok(Command::Reject),
             x => Err(BitcoinMessageError::CommandNameUnknown(x.to_string())),
         }
     }
 }
 
+impl TryFrom<[u8; COMMAND_NAME_SIZE]> for Command {
+    type Error = BitcoinMessageError;
+
+    /// Parses the raw, null padded `command_name` field of a message header.
+    fn try_from(value: [u8; COMMAND_NAME_SIZE]) -> Result<Self, Self::Error> {
+        command_name(&value)?.try_into()
+    }
+}
+
+/// Returns the name in the raw `command_name` field of a message header, without resolving the [`Command`].
+///
+/// The name ends at the first null, and has to be ASCII. The padding after it has to be all nulls.
+pub(crate) fn command_name(bytes: &[u8; COMMAND_NAME_SIZE]) -> Result<&str, BitcoinMessageError> {
+    let len = bytes
+        .iter()
+        .position(|b| *b == 0x00)
+        .unwrap_or(COMMAND_NAME_SIZE);
+    let (name, padding) = bytes.split_at(len);
+    if name.is_empty() {
+        return Err(BitcoinMessageError::EmptyCommandName);
+    }
+    let name = match core::str::from_utf8(name) {
+        Ok(name) if name.is_ascii() => name,
+        _ => return Err(BitcoinMessageError::CommandNameNonAscii),
+    };
+    if padding.iter().any(|b| *b != 0x00) {
+        return Err(BitcoinMessageError::MalformedCommandPadding(
+            name.to_string(),
+        ));
+    }
+
+    Ok(name)
+}
+
+/// Reason for rejecting a message, as sent in `reject` messages. See [bitcoin docs](https://developer.bitcoin.org/reference/p2p_networking.html#reject).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RejectCode {
+    /// The message could not be decoded.
+    Malformed = 0x01,
+
+    /// The block or transaction is invalid.
+    Invalid = 0x10,
+
+    /// The message or block version is no longer supported.
+    Obsolete = 0x11,
+
+    /// The transaction is a duplicate, e.g. a double spend.
+    Duplicate = 0x12,
+
+    /// The transaction is not standard.
+    NonStandard = 0x40,
+
+    /// One or more outputs are below the dust threshold.
+    Dust = 0x41,
+
+    /// The transaction fee is too low.
+    InsufficientFee = 0x42,
+
+    /// The block conflicts with a checkpoint.
+    Checkpoint = 0x43,
+}
+
+impl TryFrom<u8> for RejectCode {
+    type Error = BitcoinMessageError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(RejectCode::Malformed),
+            0x10 => Ok(RejectCode::Invalid),
+            0x11 => Ok(RejectCode::Obsolete),
+            0x12 => Ok(RejectCode::Duplicate),
+            0x40 => Ok(RejectCode::NonStandard),
+            0x41 => Ok(RejectCode::Dust),
+            0x42 => Ok(RejectCode::InsufficientFee),
+            0x43 => Ok(RejectCode::Checkpoint),
+            x => Err(BitcoinMessageError::RejectCodeUnknown(x)),
+        }
+    }
+}
+
+impl Display for RejectCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let s = match self {
+            RejectCode::Malformed => "malformed",
+            RejectCode::Invalid => "invalid",
+            RejectCode::Obsolete => "obsolete",
+            RejectCode::Duplicate => "duplicate",
+            RejectCode::NonStandard => "non-standard",
+            RejectCode::Dust => "dust",
+            RejectCode::InsufficientFee => "insufficient fee",
+            RejectCode::Checkpoint => "checkpoint",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Bitcoin network, identified by the `start_string` of its messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    /// The main network.
+    Mainnet,
+
+    /// The test network (testnet3).
+    Testnet,
+
+    /// The regression test network.
+    Regtest,
+
+    /// The default signet. Custom signets, with their own challenge, have their own magic: use
+    /// [`Network::Custom`] for them.
+    Signet,
+
+    /// A private network or custom signet, with non-standard magic.
+    Custom {
+        /// The `start_string` magic bytes.
+        magic: [u8; 4],
+
+        /// The default TCP port.
+        port: u16,
+    },
+}
+
+impl Network {
+    /// Returns the `start_string` magic bytes of this network.
+    pub const fn magic(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Custom { magic, .. } => *magic,
+        }
+    }
+
+    /// Returns the network with the given `start_string` magic bytes, if it's a known one. Never returns
+    /// [`Network::Custom`], as its magic can't be told apart from garbage.
+    pub const fn from_magic(bytes: [u8; 4]) -> Option<Network> {
+        match bytes {
+            [0xf9, 0xbe, 0xb4, 0xd9] => Some(Network::Mainnet),
+            [0x0b, 0x11, 0x09, 0x07] => Some(Network::Testnet),
+            [0xfa, 0xbf, 0xb5, 0xda] => Some(Network::Regtest),
+            [0x0a, 0x03, 0xcf, 0x40] => Some(Network::Signet),
+            _ => None,
+        }
+    }
+
+    /// Returns the default TCP port of this network.
+    pub const fn default_port(&self) -> u16 {
+        match self {
+            Network::Mainnet => 8333,
+            Network::Testnet => 18333,
+            Network::Regtest => 18444,
+            Network::Signet => 38333,
+            Network::Custom { port, .. } => *port,
+        }
+    }
+
+    /// Returns the well-known DNS seeds of this network, resolving to addresses of its nodes. Empty for
+    /// [`Network::Regtest`] and [`Network::Custom`].
+    pub const fn dns_seeds(&self) -> &'static [&'static str] {
+        match self {
+            Network::Mainnet => &[
+                "seed.bitcoin.sipa.be",
+                "dnsseed.bluematt.me",
+                "dnsseed.bitcoin.dashjr-list-of-p2p-nodes.us",
+                "seed.bitcoinstats.com",
+                "seed.bitcoin.jonasschnelli.ch",
+                "seed.btc.petertodd.net",
+                "seed.bitcoin.sprovoost.nl",
+                "dnsseed.emzy.de",
+                "seed.bitcoin.wiz.biz",
+            ],
+            Network::Testnet => &[
+                "testnet-seed.bitcoin.jonasschnelli.ch",
+                "seed.tbtc.petertodd.net",
+                "seed.testnet.bitcoin.sprovoost.nl",
+                "testnet-seed.bluematt.me",
+            ],
+            Network::Signet => &["seed.signet.bitcoin.sprovoost.nl"],
+            Network::Regtest | Network::Custom { .. } => &[],
+        }
+    }
+}
+
 impl From<Command> for String {
     fn from(c: Command) -> Self {
         c.to_string()
@@ -83,23 +518,285 @@ impl From<Command> for Vec<u8> {
 
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
-mod tests {
+pub(crate) mod tests {
     use quickcheck::Arbitrary;
+    use std::{collections::HashMap, net::SocketAddr};
 
     use super::*;
 
+    /// Every [`Command`], in declaration order.
+    pub(crate) const ALL_COMMANDS: [Command; 20] = [
+        Command::Version,
+        Command::VerAck,
+        Command::Inv,
+        Command::GetData,
+        Command::NotFound,
+        Command::Block,
+        Command::Ping,
+        Command::Pong,
+        Command::GetCfCheckpt,
+        Command::CfCheckpt,
+        Command::GetHeaders,
+        Command::Headers,
+        Command::WtxidRelay,
+        Command::SendAddrV2,
+        Command::SendHeaders,
+        Command::SendCmpct,
+        Command::FeeFilter,
+        Command::Addr,
+        Command::GetAddr,
+        Command::Reject,
+    ];
+
     impl Arbitrary for ServiceIdentifier {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            Self::from_bits_truncate(u64::arbitrary(g))
+            Self::from_bits_retain(u64::arbitrary(g))
         }
     }
 
     impl Arbitrary for Command {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            *g.choose(&[Command::Version, Command::VerAck]).unwrap()
+            *g.choose(&ALL_COMMANDS).unwrap()
         }
     }
 
+    impl Arbitrary for RejectCode {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            *g.choose(&[
+                RejectCode::Malformed,
+                RejectCode::Invalid,
+                RejectCode::Obsolete,
+                RejectCode::Duplicate,
+                RejectCode::NonStandard,
+                RejectCode::Dust,
+                RejectCode::InsufficientFee,
+                RejectCode::Checkpoint,
+            ])
+            .unwrap()
+        }
+    }
+
+    #[test]
+    fn service_identifier_as_map_key() {
+        let mut peers: HashMap<ServiceIdentifier, Vec<SocketAddr>> = HashMap::new();
+        let full = ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS;
+        let limited = ServiceIdentifier::NODE_NETWORK_LIMITED;
+        let addr = SocketAddr::from(([127, 0, 0, 1], 8333));
+
+        peers.entry(full).or_default().push(addr);
+        peers.entry(limited).or_default().push(addr);
+        peers.entry(full).or_default().push(addr);
+
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[&full].len(), 2);
+        assert_eq!(peers[&limited].len(), 1);
+    }
+
+    #[test]
+    fn service_identifier_presets() {
+        assert_eq!(
+            ServiceIdentifier::FULL_NODE.bits(),
+            (ServiceIdentifier::NODE_NETWORK
+                | ServiceIdentifier::NODE_WITNESS
+                | ServiceIdentifier::NODE_BLOOM
+                | ServiceIdentifier::NODE_NETWORK_LIMITED)
+                .bits()
+        );
+        assert_eq!(
+            ServiceIdentifier::PRUNED_NODE.bits(),
+            (ServiceIdentifier::NODE_WITNESS | ServiceIdentifier::NODE_NETWORK_LIMITED).bits()
+        );
+        assert_eq!(ServiceIdentifier::all_of(&[]), ServiceIdentifier::UNNAMED);
+    }
+
+    #[test]
+    fn command_from_raw_bytes() {
+        assert_eq!(
+            Command::try_from(*b"verack\0\0\0\0\0\0").unwrap(),
+            Command::VerAck
+        );
+        assert_eq!(
+            Command::try_from(*b"getcfcheckpt").unwrap(),
+            Command::GetCfCheckpt
+        );
+        assert!(matches!(
+            Command::try_from(*b"foo\0\0\0\0\0\0\0\0\0"),
+            Err(BitcoinMessageError::CommandNameUnknown(c)) if c == "foo"
+        ));
+        assert!(matches!(
+            Command::try_from(*b"ver\xe4ck\0\0\0\0\0\0"),
+            Err(BitcoinMessageError::CommandNameNonAscii)
+        ));
+        assert!(matches!(
+            Command::try_from([0u8; COMMAND_NAME_SIZE]),
+            Err(BitcoinMessageError::EmptyCommandName)
+        ));
+        assert!(matches!(
+            Command::try_from(*b"ping\0\0\0\0\0\0\0x"),
+            Err(BitcoinMessageError::MalformedCommandPadding(c)) if c == "ping"
+        ));
+    }
+
+    #[test]
+    fn dns_seeds_differ_per_network() {
+        let mainnet = Network::Mainnet.dns_seeds();
+
+        assert!(mainnet.contains(&"seed.bitcoin.sipa.be"));
+        assert!(!Network::Testnet.dns_seeds().is_empty());
+        assert!(Network::Testnet
+            .dns_seeds()
+            .iter()
+            .all(|s| !mainnet.contains(s)));
+        assert!(Network::Regtest.dns_seeds().is_empty());
+    }
+
+    #[test]
+    fn network_magic_roundtrip() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet,
+            Network::Regtest,
+            Network::Signet,
+        ] {
+            assert_eq!(Network::from_magic(network.magic()), Some(network));
+        }
+        assert_eq!(Network::from_magic([0x00, 0x01, 0x02, 0x03]), None);
+    }
+
+    #[test]
+    fn negotiation_commands() {
+        let negotiation = [
+            Command::WtxidRelay,
+            Command::SendAddrV2,
+            Command::SendHeaders,
+            Command::SendCmpct,
+        ];
+
+        for command in ALL_COMMANDS {
+            assert_eq!(
+                command.is_negotiation(),
+                negotiation.contains(&command),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn unnamed_services_requirements() {
+        let none = ServiceIdentifier::UNNAMED;
+        let full = ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS;
+
+        assert!(none.satisfies(ServiceIdentifier::UNNAMED));
+        assert!(!none.satisfies(ServiceIdentifier::NODE_NETWORK));
+        assert_eq!(none.missing(full), full);
+        assert_eq!(
+            none.missing(ServiceIdentifier::UNNAMED),
+            ServiceIdentifier::UNNAMED
+        );
+        assert!(full.satisfies(ServiceIdentifier::UNNAMED));
+        assert!(full.satisfies(ServiceIdentifier::NODE_WITNESS));
+        assert_eq!(
+            ServiceIdentifier::NODE_NETWORK.missing(full),
+            ServiceIdentifier::NODE_WITNESS
+        );
+        assert_eq!(full.missing(full), ServiceIdentifier::UNNAMED);
+    }
+
+    #[test]
+    fn pruned_and_full_archive() {
+        let witness = ServiceIdentifier::NODE_WITNESS;
+
+        assert!(ServiceIdentifier::PRUNED_NODE.is_pruned());
+        assert!(!ServiceIdentifier::PRUNED_NODE.is_full_archive());
+        assert!(ServiceIdentifier::FULL_NODE.is_full_archive());
+        assert!(!ServiceIdentifier::FULL_NODE.is_pruned());
+        assert!(
+            (ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_NETWORK_LIMITED)
+                .is_full_archive()
+        );
+        assert!(!witness.is_pruned());
+        assert!(!witness.is_full_archive());
+    }
+
+    #[test]
+    fn service_identifier_iter_yields_single_flags() {
+        let x = ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS;
+
+        let flags: Vec<_> = x.iter().collect();
+
+        assert_eq!(
+            flags,
+            vec![
+                ServiceIdentifier::NODE_NETWORK,
+                ServiceIdentifier::NODE_WITNESS
+            ]
+        );
+        assert_eq!(
+            flags
+                .into_iter()
+                .fold(ServiceIdentifier::UNNAMED, |a, b| a | b),
+            x
+        );
+        assert_eq!(ServiceIdentifier::UNNAMED.iter().count(), 0);
+        assert_eq!(
+            ServiceIdentifier::from_bits_retain(0x80000)
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![ServiceIdentifier::from_bits_retain(0x80000)]
+        );
+    }
+
+    #[test]
+    fn service_identifier_retains_unknown_bits() {
+        let x = ServiceIdentifier::from_bits_retain(0x80409);
+
+        assert_eq!(x.bits(), 0x80409);
+        assert_eq!(
+            x.known_bits(),
+            ServiceIdentifier::NODE_NETWORK
+                | ServiceIdentifier::NODE_WITNESS
+                | ServiceIdentifier::NODE_NETWORK_LIMITED
+        );
+        assert_eq!(x.unknown_bits(), 0x80000);
+        assert_eq!(ServiceIdentifier::NODE_NETWORK.unknown_bits(), 0);
+    }
+
+    #[test]
+    fn service_identifier_from_str() {
+        assert_eq!(
+            "NODE_NETWORK|NODE_WITNESS"
+                .parse::<ServiceIdentifier>()
+                .unwrap(),
+            ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS
+        );
+        assert_eq!(
+            " NODE_BLOOM ".parse::<ServiceIdentifier>().unwrap(),
+            ServiceIdentifier::NODE_BLOOM
+        );
+        assert_eq!(
+            "UNNAMED".parse::<ServiceIdentifier>().unwrap(),
+            ServiceIdentifier::UNNAMED
+        );
+        assert!(matches!(
+            "NODE_NETWORK|NODE_FOO".parse::<ServiceIdentifier>(),
+            Err(BitcoinMessageError::ServiceIdentifierUnknown(x)) if x == "NODE_FOO"
+        ));
+    }
+
+    #[test]
+    fn service_identifier_display() {
+        let x = ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS;
+
+        assert_eq!(x.to_string(), "NODE_NETWORK | NODE_WITNESS");
+        assert_eq!(x.to_string().parse::<ServiceIdentifier>().unwrap(), x);
+        assert_eq!(ServiceIdentifier::UNNAMED.to_string(), "UNNAMED");
+        assert_eq!(
+            ServiceIdentifier::from_bits_retain(0x80001).to_string(),
+            "NODE_NETWORK | 0x80000"
+        );
+    }
+
     #[test]
     fn command_as_string() {
         assert_eq!(Command::Version.to_string(), "version");
@@ -112,6 +809,36 @@ mod tests {
         assert_eq!(Command::try_from("verack").unwrap(), Command::VerAck);
     }
 
+    #[test]
+    fn reject_code_from_u8() {
+        let codes = [
+            (0x01, RejectCode::Malformed),
+            (0x10, RejectCode::Invalid),
+            (0x11, RejectCode::Obsolete),
+            (0x12, RejectCode::Duplicate),
+            (0x40, RejectCode::NonStandard),
+            (0x41, RejectCode::Dust),
+            (0x42, RejectCode::InsufficientFee),
+            (0x43, RejectCode::Checkpoint),
+        ];
+        for (byte, code) in codes {
+            assert_eq!(RejectCode::try_from(byte).unwrap(), code);
+            assert_eq!(code as u8, byte);
+        }
+
+        assert!(matches!(
+            RejectCode::try_from(0x02),
+            Err(BitcoinMessageError::RejectCodeUnknown(0x02))
+        ));
+        assert_eq!(RejectCode::InsufficientFee.to_string(), "insufficient fee");
+    }
+
+    #[test]
+    fn network_magic_matches_mainnet_start_string() {
+        assert_eq!(Network::Mainnet.magic(), crate::START_STRING_MAINNET);
+        assert_eq!(Network::Mainnet.default_port(), crate::PORT_MAINNET);
+    }
+
     #[test]
     fn command_as_bytes() {
         assert_eq!(Command::Version.to_bytes(), b"version");
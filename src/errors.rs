@@ -1,8 +1,19 @@
 #![allow(missing_docs)]
 
+use crate::{enums::Command, reject::RejectData, types::Hash};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+};
+use core::fmt::Display;
 use thiserror::Error;
 
+/// Error returned by all fallible operations of this crate.
+///
+/// New variants may be added in minor releases, so matches on it must include a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum BitcoinMessageError {
     #[error("command name too long")]
     CommandNameTooLong,
@@ -10,18 +21,192 @@ pub enum BitcoinMessageError {
     #[error("command name has to be ASCII string")]
     CommandNameNonAscii,
 
+    #[error("empty command name")]
+    EmptyCommandName,
+
     #[error("IO Error during (de)serialization: {0}")]
-    SerializationError(#[from] std::io::Error),
+    SerializationError(#[from] crate::io::Error),
+
+    #[error("unknown service identifier: {0}")]
+    ServiceIdentifierUnknown(String),
+
+    #[error("connection closed by peer")]
+    ConnectionClosed,
 
-    #[error("payload is larger than MAX_SIZE")]
-    PayloadTooBig,
+    #[error("payload of {size} bytes is larger than the limit of {max} bytes")]
+    PayloadTooBig { size: usize, max: usize },
+
+    #[error(
+        "`{command}` payload of {len} bytes is larger than its plausible maximum of {max} bytes"
+    )]
+    PayloadSizeUnexpected {
+        command: Command,
+        len: usize,
+        max: usize,
+    },
 
     #[error("FromUtf8Error during deserialization: {0}")]
-    Utf8DeserializationError(#[from] std::string::FromUtf8Error),
+    Utf8DeserializationError(#[from] alloc::string::FromUtf8Error),
+
+    #[error("timestamp {0} is too far from the current time")]
+    TimestampOutOfRange(i64),
+
+    #[error("negative start height: {0}")]
+    InvalidStartHeight(i32),
+
+    #[error("invalid socket address: {0}")]
+    AddressParse(#[from] core::net::AddrParseError),
+
+    #[error("command name `{0}` is followed by non-null padding")]
+    MalformedCommandPadding(String),
+
+    #[error("payload has {extra} trailing bytes")]
+    TrailingPayloadBytes { extra: usize },
+
+    #[error("payload is missing {missing} bytes")]
+    MissingPayloadBytes { missing: usize },
+
+    #[error("invalid boolean byte in `{field}`: {value:#04x}")]
+    InvalidBooleanByte { field: &'static str, value: u8 },
 
     #[error("unknown command name: {0}")]
     CommandNameUnknown(String),
 
     #[error("checksum mismatch")]
     ChecksumMismatch,
+
+    #[error("payload does not match command `{0}`")]
+    PayloadMismatch(Command),
+
+    #[error("unexpected command: {0}")]
+    UnexpectedCommand(Command),
+
+    #[error("invalid hash hex string")]
+    InvalidHashString,
+
+    #[error("unknown inventory type: {0}")]
+    InventoryTypeUnknown(u32),
+
+    #[error("inventory list of {count} items is larger than MAX_INVENTORY_LEN")]
+    InventoryTooLarge { count: usize },
+
+    #[error("address list of {count} items is larger than MAX_ADDR_LEN")]
+    AddrTooLarge { count: usize },
+
+    #[error("unknown reject code: {0:#04x}")]
+    RejectCodeUnknown(u8),
+
+    #[error("rejected by peer: {0}")]
+    Rejected(RejectData),
+
+    #[error("list of {count} headers is larger than MAX_HEADERS_LEN")]
+    HeadersTooLarge { count: usize },
+
+    #[error("timed out waiting for headers")]
+    HeadersTimeout,
+
+    #[error("invalid segwit flag: {0:#04x}")]
+    InvalidSegwitFlag(u8),
+
+    #[error("nonce conflict: connected to self")]
+    NonceConflict,
+
+    #[error("timed out waiting for block {0}")]
+    BlockTimeout(Hash),
+
+    #[error("timed out waiting for `version`")]
+    VersionTimeout,
+
+    #[error("timed out waiting for `verack`")]
+    VerAckTimeout,
+
+    #[error("peer does not have block {0}")]
+    BlockNotFound(Hash),
+
+    #[error("invalid {context}: {source}")]
+    NestedField {
+        context: String,
+        #[source]
+        source: Box<BitcoinMessageError>,
+    },
+}
+
+impl BitcoinMessageError {
+    /// Returns the wire name of the received command, if `self` means a different message than expected arrived:
+    /// for [`BitcoinMessageError::UnexpectedCommand`] and [`BitcoinMessageError::CommandNameUnknown`]. Borrowed in
+    /// both cases, so logging it doesn't allocate.
+    pub fn unexpected_command(&self) -> Option<&str> {
+        match self {
+            Self::UnexpectedCommand(c) => Some(c.as_str()),
+            Self::CommandNameUnknown(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Wraps `self` in [`BitcoinMessageError::NestedField`], prepending `field` to the path if it already is one,
+    /// e.g. `transaction[2]` and `input[1].script` make `transaction[2].input[1].script`.
+    pub(crate) fn in_field(self, field: impl Display) -> Self {
+        match self {
+            Self::NestedField { context, source } => Self::NestedField {
+                context: format!("{}.{}", field, context),
+                source,
+            },
+            source => Self::NestedField {
+                context: field.to_string(),
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_include_context() {
+        assert_eq!(
+            BitcoinMessageError::PayloadTooBig {
+                size: 40_000_000,
+                max: 33_554_432
+            }
+            .to_string(),
+            "payload of 40000000 bytes is larger than the limit of 33554432 bytes"
+        );
+        assert_eq!(
+            BitcoinMessageError::InvalidBooleanByte {
+                field: "relay",
+                value: 2
+            }
+            .to_string(),
+            "invalid boolean byte in `relay`: 0x02"
+        );
+        assert_eq!(
+            BitcoinMessageError::RejectCodeUnknown(0x11).to_string(),
+            "unknown reject code: 0x11"
+        );
+        assert_eq!(
+            BitcoinMessageError::InvalidSegwitFlag(0x02)
+                .in_field("input[1]")
+                .in_field("transaction[2]")
+                .to_string(),
+            "invalid transaction[2].input[1]: invalid segwit flag: 0x02"
+        );
+    }
+
+    #[test]
+    fn unexpected_command_names_known_and_unknown_commands() {
+        assert_eq!(
+            BitcoinMessageError::UnexpectedCommand(Command::Ping).unexpected_command(),
+            Some("ping")
+        );
+        assert_eq!(
+            BitcoinMessageError::CommandNameUnknown("foo".to_string()).unexpected_command(),
+            Some("foo")
+        );
+        assert_eq!(
+            BitcoinMessageError::ChecksumMismatch.unexpected_command(),
+            None
+        );
+    }
 }
@@ -0,0 +1,245 @@
+use crate::{
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    message::{BitcoinDeserialize, BitcoinSerialize, MAX_SIZE},
+    types::{read_vec, write_vec, Hash, VarInt, HASH_SIZE},
+};
+use alloc::vec::Vec;
+#[cfg(feature = "getset")]
+use getset::Getters;
+
+/// `getcfcheckpt` message payload: requests the filter header checkpoints up to `stop_hash`. See [BIP 157](https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki#getcfcheckpt).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct GetCfCheckpt {
+    /// The type of the filter being requested.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    filter_type: u8,
+
+    /// The hash of the last block in the requested range.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    stop_hash: Hash,
+}
+
+#[cfg(not(feature = "getset"))]
+impl GetCfCheckpt {
+    /// The type of the filter being requested.
+    #[inline(always)]
+    pub fn filter_type(&self) -> &u8 {
+        &self.filter_type
+    }
+
+    /// The hash of the last block in the requested range.
+    #[inline(always)]
+    pub fn stop_hash(&self) -> &Hash {
+        &self.stop_hash
+    }
+}
+
+impl GetCfCheckpt {
+    /// Creates new [`GetCfCheckpt`].
+    pub fn new(filter_type: u8, stop_hash: Hash) -> Self {
+        Self {
+            filter_type,
+            stop_hash,
+        }
+    }
+
+    /// Returns the length of the serialized [`GetCfCheckpt`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        1 + HASH_SIZE
+    }
+}
+
+impl BitcoinSerialize for GetCfCheckpt {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(self.serialized_len());
+        buf.write_u8(self.filter_type)?;
+        buf.write_all(self.stop_hash.as_bytes())?;
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for GetCfCheckpt {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let filter_type = data.read_u8()?;
+        let stop_hash = Hash::from_bytes(data)?;
+
+        Ok(Self {
+            filter_type,
+            stop_hash,
+        })
+    }
+}
+
+/// `cfcheckpt` message payload: filter headers at every 1000th block up to `stop_hash`. See [BIP 157](https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki#cfcheckpt).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct CfCheckpt {
+    /// The type of the filter the headers belong to.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    filter_type: u8,
+
+    /// The hash of the last block in the range.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    stop_hash: Hash,
+
+    /// The filter headers, in ascending block height order.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    filter_headers: Vec<Hash>,
+}
+
+#[cfg(not(feature = "getset"))]
+impl CfCheckpt {
+    /// The type of the filter the headers belong to.
+    #[inline(always)]
+    pub fn filter_type(&self) -> &u8 {
+        &self.filter_type
+    }
+
+    /// The hash of the last block in the range.
+    #[inline(always)]
+    pub fn stop_hash(&self) -> &Hash {
+        &self.stop_hash
+    }
+
+    /// The filter headers, in ascending block height order.
+    #[inline(always)]
+    pub fn filter_headers(&self) -> &Vec<Hash> {
+        &self.filter_headers
+    }
+}
+
+impl CfCheckpt {
+    /// Creates new [`CfCheckpt`].
+    pub fn new(filter_type: u8, stop_hash: Hash, filter_headers: Vec<Hash>) -> Self {
+        Self {
+            filter_type,
+            stop_hash,
+            filter_headers,
+        }
+    }
+
+    /// Returns the length of the serialized [`CfCheckpt`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        1 + HASH_SIZE
+            + VarInt::from(self.filter_headers.len()).len()
+            + self.filter_headers.len() * HASH_SIZE
+    }
+}
+
+impl BitcoinSerialize for CfCheckpt {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(self.serialized_len());
+        buf.write_u8(self.filter_type)?;
+        buf.write_all(self.stop_hash.as_bytes())?;
+        write_vec(
+            &mut buf,
+            &self.filter_headers,
+            MAX_SIZE / HASH_SIZE,
+            |count| BitcoinMessageError::PayloadTooBig {
+                size: count.saturating_mul(HASH_SIZE),
+                max: MAX_SIZE,
+            },
+        )?;
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for CfCheckpt {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let filter_type = data.read_u8()?;
+        let stop_hash = Hash::from_bytes(data)?;
+        let filter_headers = read_vec(data, MAX_SIZE / HASH_SIZE, |count| {
+            BitcoinMessageError::PayloadTooBig {
+                size: count.saturating_mul(HASH_SIZE),
+                max: MAX_SIZE,
+            }
+        })?;
+
+        Ok(Self {
+            filter_type,
+            stop_hash,
+            filter_headers,
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    impl Arbitrary for GetCfCheckpt {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            Self::new(u8::arbitrary(g), Hash::arbitrary(g))
+        }
+    }
+
+    impl Arbitrary for CfCheckpt {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            Self::new(u8::arbitrary(g), Hash::arbitrary(g), Vec::arbitrary(g))
+        }
+    }
+
+    #[quickcheck]
+    fn get_cf_checkpt_roundtrip(x: GetCfCheckpt) -> bool {
+        let bytes = x.to_bytes().unwrap();
+
+        GetCfCheckpt::from_bytes(&mut bytes.as_slice()).unwrap() == x
+    }
+
+    #[quickcheck]
+    fn cf_checkpt_roundtrip(x: CfCheckpt) -> bool {
+        let bytes = x.to_bytes().unwrap();
+
+        CfCheckpt::from_bytes(&mut bytes.as_slice()).unwrap() == x
+    }
+
+    #[test]
+    fn cf_checkpt_serialization() {
+        let x = CfCheckpt::new(
+            0,
+            Hash::new([0xaa; 32]),
+            vec![Hash::new([0x11; 32]), Hash::new([0x22; 32])],
+        );
+
+        let bytes = x.to_bytes().unwrap();
+
+        assert_eq!(bytes.len(), 1 + 32 + 1 + 2 * 32);
+        assert_eq!(bytes[0], 0);
+        assert_eq!(bytes[1..33], [0xaa; 32]);
+        assert_eq!(bytes[33], 2);
+        assert_eq!(bytes[34..66], [0x11; 32]);
+        assert_eq!(bytes[66..], [0x22; 32]);
+        assert_eq!(
+            CfCheckpt::from_bytes(&mut bytes.as_slice())
+                .unwrap()
+                .filter_headers()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn cf_checkpt_count_over_limit() {
+        let mut data = vec![0u8; 33];
+        data.extend_from_slice(&hex!("ffffffffffffffff0f"));
+
+        assert!(matches!(
+            CfCheckpt::from_bytes(&mut data.as_slice()),
+            Err(BitcoinMessageError::PayloadTooBig { .. })
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    block::{BlockHeader, BLOCK_HEADER_SIZE},
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    message::{BitcoinDeserialize, BitcoinSerialize},
+    types::{read_vec, write_vec, Hash, VarInt, HASH_SIZE},
+};
+use alloc::vec::Vec;
+use byteorder::LittleEndian;
+#[cfg(feature = "getset")]
+use getset::Getters;
+
+/// Maximum number of [`BlockHeader`]s in a single `headers` message. Fewer headers mean the peer's tip was reached.
+pub const MAX_HEADERS_LEN: usize = 2000;
+
+/// `getheaders` message payload: requests the headers following the first hash of `locator` known to the peer.
+/// See [bitcoin docs](https://developer.bitcoin.org/reference/p2p_networking.html#getheaders).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct GetHeaders {
+    /// The protocol version of the transmitting node.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    version: u32,
+
+    /// Block locator: hashes of known blocks, from the tip backwards. See [`block_locator`].
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    locator: Vec<Hash>,
+
+    /// The hash of the last header requested; all zeroes to request as many as possible.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    stop_hash: Hash,
+}
+
+#[cfg(not(feature = "getset"))]
+impl GetHeaders {
+    /// The protocol version of the transmitting node.
+    #[inline(always)]
+    pub fn version(&self) -> &u32 {
+        &self.version
+    }
+
+    /// Block locator: hashes of known blocks, from the tip backwards. See [`block_locator`].
+    #[inline(always)]
+    pub fn locator(&self) -> &Vec<Hash> {
+        &self.locator
+    }
+
+    /// The hash of the last header requested; all zeroes to request as many as possible.
+    #[inline(always)]
+    pub fn stop_hash(&self) -> &Hash {
+        &self.stop_hash
+    }
+}
+
+impl GetHeaders {
+    /// Creates new [`GetHeaders`].
+    pub fn new(version: u32, locator: Vec<Hash>, stop_hash: Hash) -> Self {
+        Self {
+            version,
+            locator,
+            stop_hash,
+        }
+    }
+
+    /// Returns the length of the serialized [`GetHeaders`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        4 + VarInt::from(self.locator.len()).len() + (self.locator.len() + 1) * HASH_SIZE
+    }
+}
+
+impl BitcoinSerialize for GetHeaders {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(self.serialized_len());
+        buf.write_u32::<LittleEndian>(self.version)?;
+        write_vec(&mut buf, &self.locator, MAX_HEADERS_LEN, |count| {
+            BitcoinMessageError::HeadersTooLarge { count }
+        })?;
+        buf.write_all(self.stop_hash.as_bytes())?;
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for GetHeaders {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let version = data.read_u32::<LittleEndian>()?;
+        let locator = read_vec(data, MAX_HEADERS_LEN, |count| {
+            BitcoinMessageError::HeadersTooLarge { count }
+        })?;
+        let stop_hash = Hash::from_bytes(data)?;
+
+        Ok(Self {
+            version,
+            locator,
+            stop_hash,
+        })
+    }
+}
+
+/// Returns the serialized length of a `headers` list.
+pub(crate) fn headers_len(headers: &[BlockHeader]) -> usize {
+    // every header is followed by an empty transaction count
+    VarInt::from(headers.len()).len() + headers.len() * (BLOCK_HEADER_SIZE + 1)
+}
+
+/// Serializes a header list, as used by the `headers` message.
+pub(crate) fn headers_to_bytes(headers: &[BlockHeader]) -> Result<Vec<u8>, BitcoinMessageError> {
+    if headers.len() > MAX_HEADERS_LEN {
+        return Err(BitcoinMessageError::HeadersTooLarge {
+            count: headers.len(),
+        });
+    }
+    let mut buf = Vec::with_capacity(headers_len(headers));
+    buf.write_all(&VarInt::from(headers.len()).to_bytes()?)?;
+    for header in headers {
+        buf.write_all(&header.to_bytes()?)?;
+        buf.write_u8(0x00)?;
+    }
+
+    Ok(buf)
+}
+
+/// Deserializes a header list, as used by the `headers` message.
+pub(crate) fn headers_from_bytes(
+    data: &mut impl Read,
+) -> Result<Vec<BlockHeader>, BitcoinMessageError> {
+    let count = VarInt::from_bytes(data)?.0 as usize;
+    if count > MAX_HEADERS_LEN {
+        return Err(BitcoinMessageError::HeadersTooLarge { count });
+    }
+    (0..count)
+        .map(|_| {
+            let header = BlockHeader::from_bytes(data)?;
+            VarInt::from_bytes(data)?;
+
+            Ok(header)
+        })
+        .collect()
+}
+
+/// Builds a block locator from `chain`, the block hashes ordered from the genesis block up to the tip.
+///
+/// The locator holds the 10 most recent hashes, then hashes at exponentially growing distances, and always ends with
+/// the genesis block, so a peer can find the fork point with few hashes even for long chains.
+pub fn block_locator(chain: &[Hash]) -> Vec<Hash> {
+    let mut locator = Vec::new();
+    let mut index = match chain.len().checked_sub(1) {
+        Some(i) => i,
+        None => return locator,
+    };
+    let mut step = 1;
+    loop {
+        locator.push(chain[index]);
+        if index == 0 {
+            break;
+        }
+        if locator.len() >= 10 {
+            step *= 2;
+        }
+        index = index.saturating_sub(step);
+    }
+
+    locator
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    impl Arbitrary for GetHeaders {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            Self::new(u32::arbitrary(g), Vec::arbitrary(g), Hash::arbitrary(g))
+        }
+    }
+
+    #[quickcheck]
+    fn get_headers_roundtrip(x: GetHeaders) -> bool {
+        let bytes = x.to_bytes().unwrap();
+
+        bytes.len() == x.serialized_len()
+            && GetHeaders::from_bytes(&mut bytes.as_slice()).unwrap() == x
+    }
+
+    #[quickcheck]
+    fn headers_roundtrip(headers: Vec<BlockHeader>) -> bool {
+        let bytes = headers_to_bytes(&headers).unwrap();
+
+        bytes.len() == headers_len(&headers)
+            && headers_from_bytes(&mut bytes.as_slice()).unwrap() == headers
+    }
+
+    #[test]
+    fn headers_over_limit() {
+        let headers = vec![
+            BlockHeader::new(0, Hash::default(), Hash::default(), 0, 0, 0);
+            MAX_HEADERS_LEN + 1
+        ];
+
+        assert!(matches!(
+            headers_to_bytes(&headers),
+            Err(BitcoinMessageError::HeadersTooLarge { count }) if count == MAX_HEADERS_LEN + 1
+        ));
+    }
+
+    #[test]
+    fn getheaders_locator_over_limit() {
+        let getheaders = GetHeaders::new(
+            70015,
+            vec![Hash::default(); MAX_HEADERS_LEN + 1],
+            Hash::default(),
+        );
+
+        assert!(matches!(
+            getheaders.to_bytes(),
+            Err(BitcoinMessageError::HeadersTooLarge { count }) if count == MAX_HEADERS_LEN + 1
+        ));
+    }
+
+    #[test]
+    fn block_locator_is_exponentially_spaced() {
+        let chain: Vec<Hash> = (0..100u8).map(|i| Hash::new([i; 32])).collect();
+
+        let locator = block_locator(&chain);
+
+        let heights: Vec<u8> = locator.iter().map(|h| h.as_bytes()[0]).collect();
+        assert_eq!(
+            heights,
+            vec![99, 98, 97, 96, 95, 94, 93, 92, 91, 90, 88, 84, 76, 60, 28, 0]
+        );
+        assert_eq!(block_locator(&chain[..1]), vec![chain[0]]);
+        assert!(block_locator(&[]).is_empty());
+    }
+}
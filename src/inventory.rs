@@ -0,0 +1,211 @@
+use crate::{
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    message::{BitcoinDeserialize, BitcoinSerialize},
+    types::{read_vec, write_vec, Hash, VarInt},
+};
+use alloc::vec::Vec;
+use byteorder::LittleEndian;
+#[cfg(feature = "getset")]
+use getset::Getters;
+
+/// Size of a serialized [`InventoryVector`], in bytes.
+pub const INVENTORY_VECTOR_SIZE: usize = 36;
+
+/// Maximum number of [`InventoryVector`]s in a single `inv`, `getdata` or `notfound` message.
+pub const MAX_INVENTORY_LEN: usize = 50000;
+
+/// Type of the object an [`InventoryVector`] refers to. See [bitcoin docs](https://developer.bitcoin.org/reference/p2p_networking.html#data-messages).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InventoryType {
+    /// Any data of this type may be ignored.
+    Error = 0,
+
+    /// The hash is a TXID.
+    Tx = 1,
+
+    /// The hash is of a block header.
+    Block = 2,
+
+    /// The hash is of a block header; reply with a `merkleblock` message.
+    FilteredBlock = 3,
+
+    /// The hash is of a block header; reply with a `cmpctblock` message.
+    CmpctBlock = 4,
+
+    /// The hash is a TXID; reply with a transaction including witness data.
+    WitnessTx = 0x40000001,
+
+    /// The hash is of a block header; reply with a block including witness data.
+    WitnessBlock = 0x40000002,
+}
+
+impl TryFrom<u32> for InventoryType {
+    type Error = BitcoinMessageError;
+
+    fn try_from(value: u32) -> Result<Self, BitcoinMessageError> {
+        match value {
+            0 => Ok(InventoryType::Error),
+            1 => Ok(InventoryType::Tx),
+            2 => Ok(InventoryType::Block),
+            3 => Ok(InventoryType::FilteredBlock),
+            4 => Ok(InventoryType::CmpctBlock),
+            0x40000001 => Ok(InventoryType::WitnessTx),
+            0x40000002 => Ok(InventoryType::WitnessBlock),
+            x => Err(BitcoinMessageError::InventoryTypeUnknown(x)),
+        }
+    }
+}
+
+/// Identifies an object (block, transaction, ...) in `inv`, `getdata` and `notfound` messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct InventoryVector {
+    /// The type of the object.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    inv_type: InventoryType,
+
+    /// The hash of the object.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    hash: Hash,
+}
+
+#[cfg(not(feature = "getset"))]
+impl InventoryVector {
+    /// The type of the object.
+    #[inline(always)]
+    pub fn inv_type(&self) -> &InventoryType {
+        &self.inv_type
+    }
+
+    /// The hash of the object.
+    #[inline(always)]
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
+}
+
+impl InventoryVector {
+    /// Creates new [`InventoryVector`].
+    pub fn new(inv_type: InventoryType, hash: Hash) -> Self {
+        Self { inv_type, hash }
+    }
+}
+
+impl BitcoinSerialize for InventoryVector {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(INVENTORY_VECTOR_SIZE);
+        buf.write_u32::<LittleEndian>(self.inv_type as u32)?;
+        buf.write_all(self.hash.as_bytes())?;
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for InventoryVector {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let inv_type = data.read_u32::<LittleEndian>()?.try_into()?;
+        let hash = Hash::from_bytes(data)?;
+
+        Ok(Self { inv_type, hash })
+    }
+}
+
+/// Serializes an inventory list, as used by the `inv`, `getdata` and `notfound` messages.
+pub(crate) fn inventory_to_bytes(
+    items: &[InventoryVector],
+) -> Result<Vec<u8>, BitcoinMessageError> {
+    let mut buf = Vec::with_capacity(inventory_len(items));
+    write_vec(&mut buf, items, MAX_INVENTORY_LEN, |count| {
+        BitcoinMessageError::InventoryTooLarge { count }
+    })?;
+
+    Ok(buf)
+}
+
+/// Returns the serialized length of an inventory list.
+pub(crate) fn inventory_len(items: &[InventoryVector]) -> usize {
+    VarInt::from(items.len()).len() + items.len() * INVENTORY_VECTOR_SIZE
+}
+
+/// Deserializes an inventory list, as used by the `inv`, `getdata` and `notfound` messages.
+pub(crate) fn inventory_from_bytes(
+    data: &mut impl Read,
+) -> Result<Vec<InventoryVector>, BitcoinMessageError> {
+    read_vec(data, MAX_INVENTORY_LEN, |count| {
+        BitcoinMessageError::InventoryTooLarge { count }
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    impl Arbitrary for InventoryType {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            *g.choose(&[
+                InventoryType::Error,
+                InventoryType::Tx,
+                InventoryType::Block,
+                InventoryType::FilteredBlock,
+                InventoryType::CmpctBlock,
+                InventoryType::WitnessTx,
+                InventoryType::WitnessBlock,
+            ])
+            .unwrap()
+        }
+    }
+
+    impl Arbitrary for InventoryVector {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            Self::new(InventoryType::arbitrary(g), Hash::arbitrary(g))
+        }
+    }
+
+    #[quickcheck]
+    fn inventory_roundtrip(items: Vec<InventoryVector>) -> bool {
+        let bytes = inventory_to_bytes(&items).unwrap();
+
+        inventory_from_bytes(&mut bytes.as_slice()).unwrap() == items
+    }
+
+    #[test]
+    fn inventory_vector_serialization() {
+        let x = InventoryVector::new(InventoryType::WitnessBlock, Hash::new([0xab; 32]));
+
+        assert_eq!(
+            x.to_bytes().unwrap(),
+            hex!("02000040abababababababababababababababababababababababababababababababab")
+        );
+    }
+
+    #[test]
+    fn unknown_inventory_type() {
+        let data = hex!("05000000abababababababababababababababababababababababababababababababab");
+
+        let result = InventoryVector::from_bytes(&mut data.as_slice());
+
+        assert!(matches!(
+            result,
+            Err(BitcoinMessageError::InventoryTypeUnknown(5))
+        ));
+    }
+
+    #[test]
+    fn inventory_over_limit() {
+        let items =
+            vec![InventoryVector::new(InventoryType::Tx, Hash::default()); MAX_INVENTORY_LEN + 1];
+
+        assert!(matches!(
+            inventory_to_bytes(&items),
+            Err(BitcoinMessageError::InventoryTooLarge { count }) if count == MAX_INVENTORY_LEN + 1
+        ));
+    }
+}
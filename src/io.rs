@@ -0,0 +1,165 @@
+#[cfg(feature = "std")]
+pub use byteorder::{ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+pub use std::io::{Error, Read, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::vec::Vec;
+    use byteorder::ByteOrder;
+    use core::fmt::Display;
+
+    /// Error returned by [`Read`] and [`Write`] implementations.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Error {
+        /// The reader ran out of data before the buffer was filled.
+        UnexpectedEof,
+
+        /// The writer refused to accept any more data.
+        WriteZero,
+    }
+
+    impl Display for Error {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            let s = match self {
+                Error::UnexpectedEof => "failed to fill whole buffer",
+                Error::WriteZero => "failed to write whole buffer",
+            };
+
+            write!(f, "{}", s)
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    /// `no_std` counterpart of `std::io::Read`.
+    pub trait Read {
+        /// Pulls some bytes from this source into the specified buffer, returning how many bytes were read.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+
+        /// Reads the exact number of bytes required to fill `buf`.
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<(), Error> {
+            while !buf.is_empty() {
+                match self.read(buf)? {
+                    0 => return Err(Error::UnexpectedEof),
+                    n => buf = &mut buf[n..],
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// `no_std` counterpart of `std::io::Write`.
+    pub trait Write {
+        /// Writes a buffer into this writer, returning how many bytes were written.
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error>;
+
+        /// Attempts to write an entire buffer into this writer.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<(), Error> {
+            while !buf.is_empty() {
+                match self.write(buf)? {
+                    0 => return Err(Error::WriteZero),
+                    n => buf = &buf[n..],
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let n = core::cmp::min(buf.len(), self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+
+            Ok(n)
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            (**self).read(buf)
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            (**self).write(buf)
+        }
+    }
+
+    macro_rules! read_impl {
+        ($name:ident, $t:ty, $size:literal) => {
+            /// Reads an integer of the given byte order.
+            fn $name<B: ByteOrder>(&mut self) -> Result<$t, Error> {
+                let mut buf = [0u8; $size];
+                self.read_exact(&mut buf)?;
+
+                Ok(B::$name(&buf))
+            }
+        };
+    }
+
+    macro_rules! write_impl {
+        ($name:ident, $t:ty, $size:literal) => {
+            /// Writes an integer in the given byte order.
+            fn $name<B: ByteOrder>(&mut self, n: $t) -> Result<(), Error> {
+                let mut buf = [0u8; $size];
+                B::$name(&mut buf, n);
+
+                self.write_all(&buf)
+            }
+        };
+    }
+
+    /// `no_std` counterpart of `byteorder::ReadBytesExt`.
+    pub trait ReadBytesExt: Read {
+        /// Reads an unsigned 8 bit integer.
+        fn read_u8(&mut self) -> Result<u8, Error> {
+            let mut buf = [0u8; 1];
+            self.read_exact(&mut buf)?;
+
+            Ok(buf[0])
+        }
+
+        read_impl!(read_u16, u16, 2);
+        read_impl!(read_u32, u32, 4);
+        read_impl!(read_i32, i32, 4);
+        read_impl!(read_u64, u64, 8);
+        read_impl!(read_i64, i64, 8);
+        read_impl!(read_u128, u128, 16);
+    }
+
+    impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+    /// `no_std` counterpart of `byteorder::WriteBytesExt`.
+    pub trait WriteBytesExt: Write {
+        /// Writes an unsigned 8 bit integer.
+        fn write_u8(&mut self, n: u8) -> Result<(), Error> {
+            self.write_all(&[n])
+        }
+
+        write_impl!(write_u16, u16, 2);
+        write_impl!(write_u32, u32, 4);
+        write_impl!(write_i32, i32, 4);
+        write_impl!(write_u64, u64, 8);
+        write_impl!(write_i64, i64, 8);
+        write_impl!(write_u128, u128, 16);
+    }
+
+    impl<W: Write + ?Sized> WriteBytesExt for W {}
+}
@@ -1,18 +1,68 @@
 //! `bitcoin-handshake` is a stub crate for communicating with bitcoin network. It defines [Bitcoin protocol](https://developer.bitcoin.org/reference/p2p_networking.html#p2p-network) messages as Rust data structures.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(missing_docs)]
 #![deny(clippy::unwrap_used)]
 #![deny(clippy::expect_used)]
 
+extern crate alloc;
+// the unit tests use `std` even in `no_std` builds
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
+
+/// Peer address messages.
+pub mod addr;
+
+/// Block and transaction data structures.
+pub mod block;
+
+/// Blocking handshake, for use without an async runtime.
+#[cfg(feature = "std")]
+pub mod blocking;
+
 /// Enumarations defining specific status and flags
 pub mod enums;
 
 /// Specific errors used by this crate.
 pub mod errors;
 
+/// Compact block filter messages. See [BIP 157](https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki).
+pub mod filters;
+
+/// Block header sync messages.
+pub mod headers;
+
+/// Inventory vectors, used by the `inv`, `getdata` and `notfound` messages.
+pub mod inventory;
+
+/// I/O traits used by the (de)serialization code.
+///
+/// With the `std` feature enabled (the default) these are re-exports of `std::io` and `byteorder`'s
+/// extension traits. Without it, minimal `alloc`-only equivalents are provided instead.
+pub mod io;
+
 /// Bitcoin protocol message implementation stub
 pub mod message;
 
+/// Async connection to a Bitcoin node.
+#[cfg(feature = "tokio")]
+pub mod peer;
+
+/// Reusable read buffers for many concurrent connections.
+#[cfg(feature = "tokio")]
+pub mod pool;
+
+/// Framed reader for consecutive messages in a byte stream.
+#[cfg(feature = "std")]
+pub mod reader;
+
+/// Rejection of a previously received message.
+pub mod reject;
+
+/// Primitive data types shared by multiple messages.
+pub mod types;
+
 mod utils;
 
 /// Protocol version implemented by this crate
@@ -21,6 +71,21 @@ pub const PROTOCOL_VERSION: i32 = 70015;
 /// The port of Bitcoin's mainnet
 pub const PORT_MAINNET: u16 = 8333;
 
+pub use addr::*;
+pub use block::*;
+#[cfg(feature = "std")]
+pub use blocking::*;
 pub use enums::*;
 pub use errors::*;
+pub use filters::*;
+pub use headers::*;
+pub use inventory::*;
 pub use message::*;
+#[cfg(feature = "tokio")]
+pub use peer::*;
+#[cfg(feature = "tokio")]
+pub use pool::*;
+#[cfg(feature = "std")]
+pub use reader::*;
+pub use reject::*;
+pub use types::*;
@@ -1,15 +1,24 @@
 use crate::{
-    enums::{Command, ServiceIdentifier},
+    addr::{addr_from_bytes, addr_len, addr_to_bytes, TimestampedAddress},
+    block::{Block, BlockHeader},
+    enums::{self, Command, Network, ServiceIdentifier},
     errors::BitcoinMessageError,
-    utils::{self, checksum, CHECKSUM_SIZE},
+    filters::{CfCheckpt, GetCfCheckpt},
+    headers::{headers_from_bytes, headers_len, headers_to_bytes, GetHeaders},
+    inventory::{
+        inventory_from_bytes, inventory_len, inventory_to_bytes, InventoryVector, MAX_INVENTORY_LEN,
+    },
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    reject::RejectData,
+    types::{read_array, read_var_bytes, var_bytes_len, write_var_bytes},
+    utils::{self, checksum, CHECKSUM_SIZE, EMPTY_PAYLOAD_CHECKSUM},
     PROTOCOL_VERSION,
 };
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
+use byteorder::{BigEndian, LittleEndian};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+#[cfg(feature = "getset")]
 use getset::Getters;
-use std::{
-    io::{Cursor, Read, Write},
-    net::{Ipv6Addr, SocketAddr},
-};
 
 /// `start_string` bytes for mainnnet
 pub const START_STRING_MAINNET: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
@@ -17,9 +26,45 @@ pub const START_STRING_MAINNET: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
 /// Maximum `user_agent` length in [`VersionData`]
 pub const MAX_USER_AGENT_LEN: usize = 256;
 
+/// Maximum number of characters kept by [`VersionData::user_agent_sanitized`]
+pub const USER_AGENT_DISPLAY_LEN: usize = 64;
+
+/// All-zeros `[::]:0` address, sent as the transmitting address of [`VersionData`] when the node doesn't know
+/// its own public address, like Bitcoin Core does.
+pub const UNSPECIFIED_SOCKET_ADDRESS: SocketAddr =
+    SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0));
+
+/// First protocol version with the `relay` field in [`VersionData`] (BIP37)
+pub const RELAY_MIN_VERSION: i32 = 70001;
+
+/// Default largest difference between a [`VersionData`] timestamp and the current time, in seconds.
+/// See [`VersionData::validate`].
+pub const MAX_TIME_OFFSET: u64 = 2 * 60 * 60;
+
+/// Largest distance into the future of the timestamp of a [`VersionData`] being sent, in seconds.
+/// See [`VersionData::checked_timestamp`].
+pub const MAX_TIMESTAMP_LEAD: i64 = 2 * 365 * 24 * 60 * 60;
+
+/// First protocol version supporting `addrv2` messages (BIP155)
+pub const ADDRV2_MIN_VERSION: i32 = 70016;
+
+/// Returns the protocol version in effect after exchanging `version` messages: the lower of both sides.
+pub const fn negotiated_version(ours: i32, theirs: i32) -> i32 {
+    if ours < theirs {
+        ours
+    } else {
+        theirs
+    }
+}
+
 /// Max payload size, as per Bitcoin protocol docs
-const MAX_SIZE: usize = 32 * 1024 * 1024;
-const COMMAND_NAME_SIZE: usize = 12;
+pub const MAX_SIZE: usize = 32 * 1024 * 1024;
+/// Plausible max payload size of the commands with small, (nearly) fixed-size payloads, like `version`. Leaves room
+/// for trailing fields added by newer protocol versions. See [`Command::max_payload_len`].
+pub const MAX_SMALL_PAYLOAD_SIZE: usize = 1024;
+/// Size of the null padded `command_name` field of a message header
+pub const COMMAND_NAME_SIZE: usize = 12;
+pub(crate) const HEADER_SIZE: usize = 24;
 
 /// Trait defining a data structure that can be serialized to bitcoin protocol "wire" data without any outside input.
 pub trait BitcoinSerialize {
@@ -32,42 +77,287 @@ pub trait BitcoinDeserialize {
     /// Constructs `Self` from binary data.
     fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
     where
-        Self: std::marker::Sized;
+        Self: core::marker::Sized;
 }
 
 /// Defines a Bitcoin protocol message.
-#[derive(Getters, Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
 pub struct Message {
     /// Magic bytes indicating the originating network; used to seek to next message when stream state is unknown.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     start_string: [u8; 4],
 
     /// Identifies what message type is contained in the payload.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     command: Command,
 
     /// The payload of this message.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     payload: Payload,
 }
 
+#[cfg(not(feature = "getset"))]
+impl Message {
+    /// Magic bytes indicating the originating network; used to seek to next message when stream state is unknown.
+    #[inline(always)]
+    pub fn start_string(&self) -> &[u8; 4] {
+        &self.start_string
+    }
+
+    /// Identifies what message type is contained in the payload.
+    #[inline(always)]
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// The payload of this message.
+    #[inline(always)]
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+}
+
 impl Message {
     /// Creates new [`Message`].
+    ///
+    /// In debug builds, panics if `payload` doesn't match `command`. See [`Message::new_checked`] for a fallible
+    /// variant, and [`Payload::matches_command`].
     pub fn new(start_string: [u8; 4], command: Command, payload: Payload) -> Self {
+        debug_assert!(
+            payload.matches_command(&command),
+            "payload does not match command `{}`",
+            command
+        );
         Self {
             start_string,
             command,
             payload,
         }
     }
+
+    /// Creates new [`Message`], failing with [`BitcoinMessageError::PayloadMismatch`] if `payload` doesn't match
+    /// `command`, e.g. `version` with an empty payload.
+    pub fn new_checked(
+        start_string: [u8; 4],
+        command: Command,
+        payload: Payload,
+    ) -> Result<Self, BitcoinMessageError> {
+        if !payload.matches_command(&command) {
+            return Err(BitcoinMessageError::PayloadMismatch(command));
+        }
+
+        Ok(Self::new(start_string, command, payload))
+    }
+}
+
+impl Message {
+    /// Reads the next [`Message`] from `reader`, e.g. a blocking `std::net::TcpStream`. Same as
+    /// [`Message::from_bytes`].
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, BitcoinMessageError> {
+        Self::from_bytes(reader)
+    }
+
+    /// Serializes this message and writes it to `writer` with a single `write_all`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), BitcoinMessageError> {
+        writer.write_all(&self.to_bytes()?)?;
+
+        Ok(())
+    }
+
+    /// Computes the checksum of the serialized payload, as written to the message header by [`Message::to_bytes`].
+    ///
+    /// For a message decoded with [`Message::from_bytes`] this matches the verified header checksum, as long as the
+    /// payload re-serializes to the received bytes (which is not the case e.g. when optional fields were absent).
+    pub fn payload_checksum(&self) -> Result<[u8; CHECKSUM_SIZE], BitcoinMessageError> {
+        Ok(checksum(&self.payload.to_bytes()?))
+    }
+
+    /// Like [`Message::from_bytes`], but fails with [`BitcoinMessageError::TrailingPayloadBytes`] if the payload
+    /// contains more bytes than its parser consumed. [`Message::from_bytes`] ignores them, for forward compatibility.
+    ///
+    /// Boolean fields, like the `relay` of `version`, also have to be `0x00` or `0x01`, failing with
+    /// [`BitcoinMessageError::InvalidBooleanByte`] otherwise. [`Message::from_bytes`] treats any non-zero byte as
+    /// `true`.
+    pub fn from_bytes_strict(data: &mut impl Read) -> Result<Self, BitcoinMessageError> {
+        Header::from_bytes(data)?.read_payload(data, true)
+    }
+
+    /// Like [`Message::from_bytes`], but keeps the payload undecoded, as [`Payload::Raw`].
+    ///
+    /// Meant for proxies forwarding messages, which re-encode to exactly the received bytes whether or not this crate
+    /// can decode their payload. The checksum is still verified, and the command has to be known.
+    pub fn from_bytes_raw(data: &mut impl Read) -> Result<Self, BitcoinMessageError> {
+        let header = Header::from_bytes(data)?;
+        let payload = header.read_payload_bytes(data)?;
+
+        Ok(Message {
+            start_string: header.start_string,
+            command: header.command()?,
+            payload: Payload::Raw(payload),
+        })
+    }
+
+    /// Returns a reader over the undecoded payload bytes, if the payload is [`Payload::Raw`], e.g. as read by
+    /// [`Message::from_bytes_raw`]. Lets custom parsers decode payloads this crate doesn't model.
+    pub fn payload_reader(&self) -> Option<impl Read + '_> {
+        match &self.payload {
+            Payload::Raw(bytes) => Some(bytes.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Splits a request for `items` into as many `getdata` messages on `network` as needed to keep each of them
+    /// within [`MAX_INVENTORY_LEN`]. Returns no messages if `items` is empty.
+    pub fn getdata_batches(items: Vec<InventoryVector>, network: Network) -> Vec<Message> {
+        items
+            .chunks(MAX_INVENTORY_LEN)
+            .map(|chunk| {
+                Message::new(
+                    network.magic(),
+                    Command::GetData,
+                    Payload::GetData(chunk.to_vec()),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the [`Network`] this message was sent on, if its `start_string` is a known one.
+    pub fn network(&self) -> Option<Network> {
+        Network::from_magic(self.start_string)
+    }
+
+    /// Returns a copy of this message for `network`: with its magic bytes, and the same command and payload.
+    pub fn clone_with_network(&self, network: Network) -> Message {
+        Self {
+            start_string: network.magic(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the wire name of the command, e.g. for logging.
+    ///
+    /// Decoded messages always have a known [`Command`], so this never allocates. The name of an unknown command
+    /// is only available from the error, see [`BitcoinMessageError::unexpected_command`].
+    pub fn command_str(&self) -> Cow<'static, str> {
+        Cow::Borrowed(self.command.as_str())
+    }
+
+    /// Returns `true` if receiving this message completes the handshake, i.e. for `verack`.
+    /// See [`Command::is_negotiation`] for the messages that may precede it.
+    pub fn is_handshake_complete_trigger(&self) -> bool {
+        self.command == Command::VerAck
+    }
+
+    /// Returns the `version` of the other side, from the message received in its place during the handshake.
+    ///
+    /// Fails with [`BitcoinMessageError::Rejected`] for `reject`, with [`BitcoinMessageError::UnexpectedCommand`] for
+    /// any other message, and with [`BitcoinMessageError::NonceConflict`] if the nonce is `local_nonce`.
+    #[cfg(feature = "std")]
+    pub(crate) fn into_remote_version(
+        self,
+        local_nonce: Option<u64>,
+    ) -> Result<VersionData, BitcoinMessageError> {
+        match self.payload {
+            Payload::Version(version) if Some(version.nonce) == local_nonce => {
+                Err(BitcoinMessageError::NonceConflict)
+            }
+            Payload::Version(version) => Ok(version),
+            Payload::Reject(reject) => Err(BitcoinMessageError::Rejected(reject)),
+            _ => Err(BitcoinMessageError::UnexpectedCommand(self.command)),
+        }
+    }
+
+    /// Returns `true` if this message, received while waiting for `verack`, completes the handshake, and `false` for
+    /// the feature negotiation messages which may precede it.
+    ///
+    /// Fails with [`BitcoinMessageError::Rejected`] for `reject`, and with
+    /// [`BitcoinMessageError::UnexpectedCommand`] for any other message.
+    #[cfg(feature = "std")]
+    pub(crate) fn completes_handshake(&self) -> Result<bool, BitcoinMessageError> {
+        match (&self.payload, self.command) {
+            (Payload::Reject(reject), _) => Err(BitcoinMessageError::Rejected(reject.clone())),
+            _ if self.is_handshake_complete_trigger() => Ok(true),
+            (_, c) if c.is_negotiation() => Ok(false),
+            (_, c) => Err(BitcoinMessageError::UnexpectedCommand(c)),
+        }
+    }
+
+    /// Returns the length of the serialized [`Message`], in bytes, without serializing it.
+    pub fn serialized_len(&self) -> Result<usize, BitcoinMessageError> {
+        Ok(HEADER_SIZE + self.payload.byte_len()?)
+    }
+
+    /// Returns an iterator decoding consecutive [`Message`]s from `data`, e.g. when a peer batches them.
+    ///
+    /// The iterator stops at the end of `data`, or after yielding the first error.
+    pub fn iter_from_bytes(data: &[u8]) -> MessageIter<'_> {
+        MessageIter {
+            data,
+            failed: false,
+        }
+    }
+}
+
+/// Iterator over [`Message`]s in a byte buffer. See [`Message::iter_from_bytes`].
+#[derive(Debug, Clone)]
+pub struct MessageIter<'a> {
+    data: &'a [u8],
+    failed: bool,
+}
+
+impl Iterator for MessageIter<'_> {
+    type Item = Result<Message, BitcoinMessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.data.is_empty() {
+            return None;
+        }
+        let result = Message::from_bytes(&mut self.data);
+        self.failed = result.is_err();
+
+        Some(result)
+    }
+}
+
+impl core::iter::FusedIterator for MessageIter<'_> {}
+
+/// Verifies the header of the serialized message in `raw` against its payload, without decoding the payload.
+///
+/// Meant for forwarding frames as-is, so the command doesn't have to be known. Fails with
+/// [`BitcoinMessageError::ChecksumMismatch`] if the checksum doesn't match, and with
+/// [`BitcoinMessageError::MissingPayloadBytes`] or [`BitcoinMessageError::TrailingPayloadBytes`] if `raw` is
+/// shorter or longer than the declared payload length.
+pub fn verify_frame(raw: &[u8]) -> Result<(), BitcoinMessageError> {
+    let mut payload = raw;
+    let header = Header::from_bytes(&mut payload)?;
+    if payload.len() < header.payload_len {
+        return Err(BitcoinMessageError::MissingPayloadBytes {
+            missing: header.payload_len - payload.len(),
+        });
+    }
+    if payload.len() > header.payload_len {
+        return Err(BitcoinMessageError::TrailingPayloadBytes {
+            extra: payload.len() - header.payload_len,
+        });
+    }
+    if header.checksum != checksum(payload) {
+        return Err(BitcoinMessageError::ChecksumMismatch);
+    }
+
+    Ok(())
 }
 
 impl BitcoinSerialize for Message {
     fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
         let mut payload = self.payload.to_bytes()?;
         let payload_len = payload.len();
-        let payload_checksum = checksum(&payload);
-        let mut buf = Vec::with_capacity(24 + payload.len());
+        let payload_checksum = if payload.is_empty() {
+            EMPTY_PAYLOAD_CHECKSUM
+        } else {
+            checksum(&payload)
+        };
+        let mut buf = Vec::with_capacity(HEADER_SIZE + payload_len);
         buf.write_all(&self.start_string)?;
         let mut command_bytes = self.command.to_bytes();
         let command_bytes_len = command_bytes.len();
@@ -86,37 +376,106 @@ impl BitcoinSerialize for Message {
 impl BitcoinDeserialize for Message {
     fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
     where
-        Self: std::marker::Sized,
+        Self: core::marker::Sized,
+    {
+        Header::from_bytes(data)?.read_payload(data, false)
+    }
+}
+
+/// Message header; everything that precedes the payload on the wire.
+pub(crate) struct Header {
+    pub(crate) start_string: [u8; 4],
+    pub(crate) command_name: [u8; COMMAND_NAME_SIZE],
+    pub(crate) payload_len: usize,
+    pub(crate) checksum: [u8; CHECKSUM_SIZE],
+}
+
+impl Header {
+    /// Resolves the [`Command`] of this header.
+    pub(crate) fn command(&self) -> Result<Command, BitcoinMessageError> {
+        self.command_name.try_into()
+    }
+
+    /// Reads the payload this header describes from `data`, verifying its checksum.
+    fn read_payload_bytes(&self, data: &mut impl Read) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut payload_bytes = vec![0u8; self.payload_len];
+        data.read_exact(&mut payload_bytes)?;
+        if self.checksum != utils::checksum(&payload_bytes) {
+            return Err(BitcoinMessageError::ChecksumMismatch);
+        }
+
+        Ok(payload_bytes)
+    }
+
+    /// Reads the payload this header describes from `data`, and assembles the [`Message`].
+    ///
+    /// The payload is consumed even if the command is unknown, so the stream stays aligned at the next message.
+    /// With `strict`, payload bytes not consumed by the payload parser, and invalid boolean bytes, are an error.
+    pub(crate) fn read_payload(
+        self,
+        data: &mut impl Read,
+        strict: bool,
+    ) -> Result<Message, BitcoinMessageError> {
+        let payload_bytes = self.read_payload_bytes(data)?;
+        let command = self.command()?;
+        let mut remaining = payload_bytes.as_slice();
+        let payload = Payload::decode(&mut remaining, &command, strict)?;
+        if strict && !remaining.is_empty() {
+            return Err(BitcoinMessageError::TrailingPayloadBytes {
+                extra: remaining.len(),
+            });
+        }
+
+        Ok(Message {
+            start_string: self.start_string,
+            command,
+            payload,
+        })
+    }
+}
+
+impl BitcoinDeserialize for Header {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
     {
         let mut start_string = [0u8; 4];
-        data.read_exact(&mut start_string)?;
-        let mut command_name_bytes = vec![0u8; COMMAND_NAME_SIZE];
-        data.read_exact(&mut command_name_bytes)?;
-        let command_name = String::from_utf8(command_name_bytes)?;
-        let command_name = command_name.replace('\0', "");
-        let command: Command = command_name.as_str().try_into()?;
+        // no data at all, as opposed to a truncated message, means the other side is gone
+        if data.read(&mut start_string[..1])? == 0 {
+            return Err(BitcoinMessageError::ConnectionClosed);
+        }
+        data.read_exact(&mut start_string[1..])?;
+        let command_name: [u8; COMMAND_NAME_SIZE] = read_array(data)?;
+        // a garbled name is reported right away, while unknown commands are reported after their payload is read
+        enums::command_name(&command_name)?;
         let payload_len = data.read_u32::<LittleEndian>()? as usize;
         if payload_len > MAX_SIZE {
-            return Err(BitcoinMessageError::PayloadTooBig);
+            return Err(BitcoinMessageError::PayloadTooBig {
+                size: payload_len,
+                max: MAX_SIZE,
+            });
         }
-        let mut checksum = vec![0u8; CHECKSUM_SIZE];
-        data.read_exact(&mut checksum)?;
-        let mut payload_bytes = vec![0u8; payload_len];
-        data.read_exact(&mut payload_bytes)?;
-        if checksum != utils::checksum(&payload_bytes) {
-            return Err(BitcoinMessageError::ChecksumMismatch);
+        if let Ok(command) = Command::try_from(command_name) {
+            if payload_len > command.max_payload_len() {
+                return Err(BitcoinMessageError::PayloadSizeUnexpected {
+                    command,
+                    len: payload_len,
+                    max: command.max_payload_len(),
+                });
+            }
         }
-        let payload = Payload::from_bytes(&mut Cursor::new(payload_bytes), &command)?;
+        let checksum = read_array(data)?;
 
         Ok(Self {
             start_string,
-            command,
-            payload,
+            command_name,
+            payload_len,
+            checksum,
         })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Bitcoin's Message payload.
 pub enum Payload {
     /// An empty payload.
@@ -124,6 +483,52 @@ pub enum Payload {
 
     /// Payload of `version` command
     Version(VersionData),
+
+    /// Payload of `inv` command
+    Inv(Vec<InventoryVector>),
+
+    /// Payload of `getdata` command
+    GetData(Vec<InventoryVector>),
+
+    /// Payload of `notfound` command
+    NotFound(Vec<InventoryVector>),
+
+    /// Payload of `block` command
+    Block(Block),
+
+    /// Payload of `ping` command: a nonce, to be echoed back in `pong`
+    Ping(u64),
+
+    /// Payload of `pong` command: the nonce of the `ping` being answered
+    Pong(u64),
+
+    /// Payload of `getcfcheckpt` command
+    GetCfCheckpt(GetCfCheckpt),
+
+    /// Payload of `cfcheckpt` command
+    CfCheckpt(CfCheckpt),
+
+    /// Payload of `getheaders` command
+    GetHeaders(GetHeaders),
+
+    /// Payload of `headers` command
+    Headers(Vec<BlockHeader>),
+
+    /// Payload of `sendcmpct` command
+    SendCmpct(SendCmpct),
+
+    /// Payload of `feefilter` command: the minimum fee rate of transactions to relay, in satoshis per kilobyte
+    FeeFilter(u64),
+
+    /// Payload of `addr` command
+    Addr(Vec<TimestampedAddress>),
+
+    /// Payload of `reject` command
+    Reject(RejectData),
+
+    /// Undecoded payload bytes, as read by [`Message::from_bytes_raw`]. Serialized as they are, and sent with any
+    /// command.
+    Raw(Vec<u8>),
 }
 
 impl Payload {
@@ -133,10 +538,156 @@ impl Payload {
         data: &mut impl Read,
         command: &Command,
     ) -> Result<Self, BitcoinMessageError> {
+        Self::decode(data, command, false)
+    }
+
+    /// Deserializes [`Payload`], rejecting invalid boolean bytes with `strict`. See [`Message::from_bytes_strict`].
+    pub(crate) fn decode(
+        data: &mut impl Read,
+        command: &Command,
+        strict: bool,
+    ) -> Result<Self, BitcoinMessageError> {
+        match command {
+            c if c.has_empty_payload() => Ok(Payload::Empty),
+            Command::Version => Ok(Payload::Version(VersionData::decode(data, strict)?)),
+            Command::Inv => Ok(Payload::Inv(inventory_from_bytes(data)?)),
+            Command::GetData => Ok(Payload::GetData(inventory_from_bytes(data)?)),
+            Command::NotFound => Ok(Payload::NotFound(inventory_from_bytes(data)?)),
+            Command::Block => Ok(Payload::Block(Block::from_bytes(data)?)),
+            Command::Ping => Ok(Payload::Ping(data.read_u64::<LittleEndian>()?)),
+            Command::Pong => Ok(Payload::Pong(data.read_u64::<LittleEndian>()?)),
+            Command::GetCfCheckpt => Ok(Payload::GetCfCheckpt(GetCfCheckpt::from_bytes(data)?)),
+            Command::CfCheckpt => Ok(Payload::CfCheckpt(CfCheckpt::from_bytes(data)?)),
+            Command::GetHeaders => Ok(Payload::GetHeaders(GetHeaders::from_bytes(data)?)),
+            Command::Headers => Ok(Payload::Headers(headers_from_bytes(data)?)),
+            Command::SendCmpct => Ok(Payload::SendCmpct(SendCmpct::decode(data, strict)?)),
+            Command::FeeFilter => Ok(Payload::FeeFilter(data.read_u64::<LittleEndian>()?)),
+            Command::Addr => Ok(Payload::Addr(addr_from_bytes(data)?)),
+            Command::Reject => Ok(Payload::Reject(RejectData::from_bytes(data)?)),
+            c => Err(BitcoinMessageError::PayloadMismatch(*c)),
+        }
+    }
+}
+
+impl core::fmt::Display for Payload {
+    /// Formats a one-line summary, e.g. `Ping(nonce=42)`. Lists are summarized by their length.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Payload::Empty => write!(f, "Empty"),
+            Payload::Version(x) => write!(
+                f,
+                "Version(v={}, ua={:?}, services={})",
+                x.version, x.user_agent, x.services
+            ),
+            Payload::Inv(x) => write!(f, "Inv(n={})", x.len()),
+            Payload::GetData(x) => write!(f, "GetData(n={})", x.len()),
+            Payload::NotFound(x) => write!(f, "NotFound(n={})", x.len()),
+            Payload::Block(x) => write!(
+                f,
+                "Block(hash={}, txs={})",
+                x.header().block_hash(),
+                x.transactions().len()
+            ),
+            Payload::Ping(nonce) => write!(f, "Ping(nonce={})", nonce),
+            Payload::Pong(nonce) => write!(f, "Pong(nonce={})", nonce),
+            Payload::GetCfCheckpt(x) => write!(
+                f,
+                "GetCfCheckpt(type={}, stop={})",
+                x.filter_type(),
+                x.stop_hash()
+            ),
+            Payload::CfCheckpt(x) => write!(
+                f,
+                "CfCheckpt(type={}, stop={}, n={})",
+                x.filter_type(),
+                x.stop_hash(),
+                x.filter_headers().len()
+            ),
+            Payload::GetHeaders(x) => write!(
+                f,
+                "GetHeaders(locator={}, stop={})",
+                x.locator().len(),
+                x.stop_hash()
+            ),
+            Payload::Headers(x) => write!(f, "Headers(n={})", x.len()),
+            Payload::SendCmpct(x) => write!(
+                f,
+                "SendCmpct(announce={}, version={})",
+                x.announce, x.version
+            ),
+            Payload::FeeFilter(rate) => write!(f, "FeeFilter(rate={})", rate),
+            Payload::Addr(x) => write!(f, "Addr(n={})", x.len()),
+            Payload::Reject(x) => write!(f, "Reject({})", x),
+            Payload::Raw(x) => write!(f, "Raw(len={})", x.len()),
+        }
+    }
+}
+
+impl Payload {
+    /// Returns the [`VersionData`] if this is a `version` payload.
+    pub fn as_version(&self) -> Option<&VersionData> {
+        match self {
+            Payload::Version(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if this is the kind of payload sent with `command`, i.e. what [`Payload::from_bytes`] would
+    /// decode it as. [`Payload::Raw`] matches any command.
+    pub fn matches_command(&self, command: &Command) -> bool {
+        if let Payload::Raw(_) = self {
+            return true;
+        }
         match command {
-            Command::Version => Ok(Payload::Version(VersionData::from_bytes(data)?)),
-            Command::VerAck => Ok(Payload::Empty),
+            c if c.has_empty_payload() => matches!(self, Payload::Empty),
+            Command::Version => matches!(self, Payload::Version(_)),
+            Command::Inv => matches!(self, Payload::Inv(_)),
+            Command::GetData => matches!(self, Payload::GetData(_)),
+            Command::NotFound => matches!(self, Payload::NotFound(_)),
+            Command::Block => matches!(self, Payload::Block(_)),
+            Command::Ping => matches!(self, Payload::Ping(_)),
+            Command::Pong => matches!(self, Payload::Pong(_)),
+            Command::GetCfCheckpt => matches!(self, Payload::GetCfCheckpt(_)),
+            Command::CfCheckpt => matches!(self, Payload::CfCheckpt(_)),
+            Command::GetHeaders => matches!(self, Payload::GetHeaders(_)),
+            Command::Headers => matches!(self, Payload::Headers(_)),
+            Command::SendCmpct => matches!(self, Payload::SendCmpct(_)),
+            Command::FeeFilter => matches!(self, Payload::FeeFilter(_)),
+            Command::Addr => matches!(self, Payload::Addr(_)),
+            Command::Reject => matches!(self, Payload::Reject(_)),
+            _ => false,
+        }
+    }
+
+    /// Returns the length of the serialized [`Payload`], in bytes, without serializing it.
+    ///
+    /// Fails with [`BitcoinMessageError::PayloadTooBig`] when [`Payload::to_bytes`] would.
+    pub fn byte_len(&self) -> Result<usize, BitcoinMessageError> {
+        let len = match self {
+            Payload::Empty => 0,
+            Payload::Version(data) => data.serialized_len(),
+            Payload::Inv(items) | Payload::GetData(items) | Payload::NotFound(items) => {
+                inventory_len(items)
+            }
+            Payload::Block(block) => block.serialized_len(),
+            Payload::Ping(_) | Payload::Pong(_) | Payload::FeeFilter(_) => 8,
+            Payload::GetCfCheckpt(x) => x.serialized_len(),
+            Payload::CfCheckpt(x) => x.serialized_len(),
+            Payload::GetHeaders(x) => x.serialized_len(),
+            Payload::Headers(headers) => headers_len(headers),
+            Payload::SendCmpct(_) => 9,
+            Payload::Addr(items) => addr_len(items),
+            Payload::Reject(x) => x.serialized_len(),
+            Payload::Raw(bytes) => bytes.len(),
+        };
+        if len > MAX_SIZE {
+            return Err(BitcoinMessageError::PayloadTooBig {
+                size: len,
+                max: MAX_SIZE,
+            });
         }
+
+        Ok(len)
     }
 }
 
@@ -145,10 +696,27 @@ impl BitcoinSerialize for Payload {
         let data = match self {
             Payload::Empty => Ok(vec![]),
             Payload::Version(data) => data.to_bytes(),
+            Payload::Inv(items) | Payload::GetData(items) | Payload::NotFound(items) => {
+                inventory_to_bytes(items)
+            }
+            Payload::Block(block) => block.to_bytes(),
+            Payload::Ping(nonce) | Payload::Pong(nonce) => Ok(nonce.to_le_bytes().to_vec()),
+            Payload::FeeFilter(fee_rate) => Ok(fee_rate.to_le_bytes().to_vec()),
+            Payload::GetCfCheckpt(x) => x.to_bytes(),
+            Payload::CfCheckpt(x) => x.to_bytes(),
+            Payload::GetHeaders(x) => x.to_bytes(),
+            Payload::Headers(headers) => headers_to_bytes(headers),
+            Payload::SendCmpct(x) => x.to_bytes(),
+            Payload::Addr(items) => addr_to_bytes(items),
+            Payload::Reject(x) => x.to_bytes(),
+            Payload::Raw(bytes) => Ok(bytes.clone()),
         };
         if let Ok(ref d) = data {
             if d.len() > MAX_SIZE {
-                return Err(BitcoinMessageError::PayloadTooBig);
+                return Err(BitcoinMessageError::PayloadTooBig {
+                    size: d.len(),
+                    max: MAX_SIZE,
+                });
             }
         }
 
@@ -156,57 +724,140 @@ impl BitcoinSerialize for Payload {
     }
 }
 
-#[derive(Getters, Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
 /// `version` message payload.
 pub struct VersionData {
     /// The highest protocol version understood by the transmitting node.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     version: i32,
 
     /// The services supported by the transmitting node encoded as a bitfield. See the list of service codes below.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     services: ServiceIdentifier,
 
     /// The current Unix epoch time according to the transmitting node’s clock.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     timestamp: i64,
 
     /// The services supported by the receiving node as perceived by the transmitting node. Same format as the ‘services’ field above.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     addr_recv_services: ServiceIdentifier,
 
-    /// The IPv6 address of the receiving node as perceived by the transmitting node.
-    #[getset(get = "pub")]
+    /// The address of the receiving node as perceived by the transmitting node. Sent as IPv6, with IPv4-mapped
+    /// addresses decoded as IPv4; see [`VersionData::recv_ipv4`] and [`VersionData::recv_ipv6`].
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     addr_recv_socket_address: SocketAddr,
 
     /// The services supported by the transmitting node. Should be identical to the ‘services’ field above.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     addr_trans_services: ServiceIdentifier,
 
-    /// The IPv6 address of the transmitting node in big endian byte order.
-    #[getset(get = "pub")]
+    /// The address of the transmitting node. Sent as IPv6, with IPv4-mapped addresses decoded as IPv4; see
+    /// [`VersionData::trans_ipv4`] and [`VersionData::trans_ipv6`].
+    ///
+    /// When the public address isn't known, send [`UNSPECIFIED_SOCKET_ADDRESS`] rather than the local address of the
+    /// connection, which may be private.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     addr_trans_socket_address: SocketAddr,
 
     /// A random nonce which can help a node detect a connection to itself.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     nonce: u64,
 
     /// User agent as defined by BIP14.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     user_agent: String,
 
     /// The height of the transmitting node’s best block chain or, in the case of an SPV client, best block header chain.
-    #[getset(get = "pub")]
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     start_height: i32,
 
-    /// Transaction relay flag.
-    #[getset(get = "pub")]
+    /// Transaction relay flag. Defaults to `true` when absent from the wire, which is always the case for versions
+    /// lower than [`RELAY_MIN_VERSION`].
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
     relay: bool,
 }
 
+#[cfg(not(feature = "getset"))]
+impl VersionData {
+    /// The highest protocol version understood by the transmitting node.
+    #[inline(always)]
+    pub fn version(&self) -> &i32 {
+        &self.version
+    }
+
+    /// The services supported by the transmitting node encoded as a bitfield. See the list of service codes below.
+    #[inline(always)]
+    pub fn services(&self) -> &ServiceIdentifier {
+        &self.services
+    }
+
+    /// The current Unix epoch time according to the transmitting node’s clock.
+    #[inline(always)]
+    pub fn timestamp(&self) -> &i64 {
+        &self.timestamp
+    }
+
+    /// The services supported by the receiving node as perceived by the transmitting node. Same format as the ‘services’ field above.
+    #[inline(always)]
+    pub fn addr_recv_services(&self) -> &ServiceIdentifier {
+        &self.addr_recv_services
+    }
+
+    /// The address of the receiving node as perceived by the transmitting node. Sent as IPv6, with IPv4-mapped
+    /// addresses decoded as IPv4; see [`VersionData::recv_ipv4`] and [`VersionData::recv_ipv6`].
+    #[inline(always)]
+    pub fn addr_recv_socket_address(&self) -> &SocketAddr {
+        &self.addr_recv_socket_address
+    }
+
+    /// The services supported by the transmitting node. Should be identical to the ‘services’ field above.
+    #[inline(always)]
+    pub fn addr_trans_services(&self) -> &ServiceIdentifier {
+        &self.addr_trans_services
+    }
+
+    /// The address of the transmitting node. Sent as IPv6, with IPv4-mapped addresses decoded as IPv4; see
+    /// [`VersionData::trans_ipv4`] and [`VersionData::trans_ipv6`].
+    ///
+    /// When the public address isn't known, send [`UNSPECIFIED_SOCKET_ADDRESS`] rather than the local address of the
+    /// connection, which may be private.
+    #[inline(always)]
+    pub fn addr_trans_socket_address(&self) -> &SocketAddr {
+        &self.addr_trans_socket_address
+    }
+
+    /// A random nonce which can help a node detect a connection to itself.
+    #[inline(always)]
+    pub fn nonce(&self) -> &u64 {
+        &self.nonce
+    }
+
+    /// User agent as defined by BIP14.
+    #[inline(always)]
+    pub fn user_agent(&self) -> &String {
+        &self.user_agent
+    }
+
+    /// The height of the transmitting node’s best block chain or, in the case of an SPV client, best block header chain.
+    #[inline(always)]
+    pub fn start_height(&self) -> &i32 {
+        &self.start_height
+    }
+
+    /// Transaction relay flag. Defaults to `true` when absent from the wire, which is always the case for versions
+    /// lower than [`RELAY_MIN_VERSION`].
+    #[inline(always)]
+    pub fn relay(&self) -> &bool {
+        &self.relay
+    }
+}
+
 impl VersionData {
+    #[cfg(feature = "rand")]
     #[allow(clippy::too_many_arguments)]
-    /// Creates new [`VersionData`].
+    /// Creates new [`VersionData`] with a random `nonce`. Requires the `rand` feature.
     ///
     /// # Panics
     ///
@@ -215,9 +866,41 @@ impl VersionData {
         services: ServiceIdentifier,
         timestamp: i64,
         addr_recv_services: ServiceIdentifier,
-        addr_recv_socket_address: SocketAddr,
+        addr_recv_socket_address: impl Into<SocketAddr>,
+        addr_trans_services: ServiceIdentifier,
+        addr_trans_socket_address: impl Into<SocketAddr>,
+        user_agent: String,
+        start_height: i32,
+        relay: bool,
+    ) -> Self {
+        Self::with_nonce(
+            rand::random(),
+            services,
+            timestamp,
+            addr_recv_services,
+            addr_recv_socket_address,
+            addr_trans_services,
+            addr_trans_socket_address,
+            user_agent,
+            start_height,
+            relay,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Creates new [`VersionData`] with an explicit `nonce`, e.g. to reproduce a captured message exactly.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `user_agent.len()` is more than [`MAX_USER_AGENT_LEN`].
+    pub fn with_nonce(
+        nonce: u64,
+        services: ServiceIdentifier,
+        timestamp: i64,
+        addr_recv_services: ServiceIdentifier,
+        addr_recv_socket_address: impl Into<SocketAddr>,
         addr_trans_services: ServiceIdentifier,
-        addr_trans_socket_address: SocketAddr,
+        addr_trans_socket_address: impl Into<SocketAddr>,
         user_agent: String,
         start_height: i32,
         relay: bool,
@@ -229,81 +912,287 @@ impl VersionData {
             );
         }
         Self {
-            version: PROTOCOL_VERSION, // any data created by this lib, not deserialized from wire, will always be PROTCOL_VERSION
+            version: PROTOCOL_VERSION, // replaced by `with_advertised_version`, if needed
             services,
             timestamp,
             addr_recv_services,
-            addr_recv_socket_address,
+            addr_recv_socket_address: addr_recv_socket_address.into(),
             addr_trans_services,
-            addr_trans_socket_address,
-            nonce: rand::random(),
+            addr_trans_socket_address: addr_trans_socket_address.into(),
+            nonce,
             user_agent,
             start_height,
             relay,
         }
     }
-}
 
-impl BitcoinSerialize for VersionData {
-    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
-        let mut buf = Vec::with_capacity(86 + self.user_agent().len());
-        buf.write_i32::<LittleEndian>(self.version)?;
-        buf.write_u64::<LittleEndian>(self.services.bits())?;
-        buf.write_i64::<LittleEndian>(self.timestamp)?;
-        buf.write_u64::<LittleEndian>(self.addr_recv_services.bits())?;
-        buf.write_u128::<BigEndian>(u128::from_ne_bytes(
-            match self.addr_recv_socket_address.ip() {
-                std::net::IpAddr::V4(x) => x.to_ipv6_mapped(),
-                std::net::IpAddr::V6(x) => x,
-            }
-            .octets(),
-        ))?;
-        buf.write_u16::<BigEndian>(self.addr_recv_socket_address.port())?;
-        buf.write_u64::<LittleEndian>(self.addr_trans_services.bits())?;
-        buf.write_u128::<BigEndian>(u128::from_ne_bytes(
-            match self.addr_trans_socket_address.ip() {
-                std::net::IpAddr::V4(x) => x.to_ipv6_mapped(),
-                std::net::IpAddr::V6(x) => x,
-            }
-            .octets(),
-        ))?;
-        buf.write_u16::<BigEndian>(self.addr_trans_socket_address.port())?;
-        buf.write_u64::<LittleEndian>(self.nonce)?;
-        buf.write_u8(self.user_agent().len() as u8)?;
-        buf.write_all(self.user_agent().as_bytes())?;
-        buf.write_i32::<LittleEndian>(self.start_height)?;
-        buf.write_u8(self.relay.into())?;
+    /// Replaces the advertised protocol version, [`PROTOCOL_VERSION`] by default, e.g. to test how peers treat
+    /// older clients.
+    ///
+    /// The message layout follows the version, e.g. `relay` is omitted before [`RELAY_MIN_VERSION`]. Note that
+    /// peers may disconnect when the version is too old, or behave unexpectedly when it's newer than what this
+    /// crate implements.
+    pub fn with_advertised_version(mut self, version: i32) -> Self {
+        self.version = version;
 
-        Ok(buf)
+        self
     }
-}
 
-impl BitcoinDeserialize for VersionData {
-    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
-    where
-        Self: std::marker::Sized,
+    /// Returns the `user_agent`, safe to display or log even when the peer is untrusted.
+    ///
+    /// Control and invisible format characters (e.g. bidi overrides and zero-width spaces) are stripped, and the result is truncated to [`USER_AGENT_DISPLAY_LEN`] characters,
+    /// ending with `…` when truncated.
+    pub fn user_agent_sanitized(&self) -> String {
+        let mut printable = self
+            .user_agent
+            .chars()
+            .filter(|c| !c.is_control() && !is_format(*c));
+        let mut sanitized: String = printable.by_ref().take(USER_AGENT_DISPLAY_LEN).collect();
+        if printable.next().is_some() {
+            sanitized.pop();
+            sanitized.push('…');
+        }
+
+        sanitized
+    }
+
+    /// Returns the length of the serialized [`VersionData`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        let relay_len = usize::from(self.version >= RELAY_MIN_VERSION);
+
+        84 + var_bytes_len(self.user_agent.as_bytes()) + relay_len
+    }
+
+    /// Returns the IPv4 address of the receiving node, if it has one.
+    pub fn recv_ipv4(&self) -> Option<Ipv4Addr> {
+        ipv4(&self.addr_recv_socket_address)
+    }
+
+    /// Returns the address of the receiving node as IPv6, as sent on the wire. IPv4 addresses are IPv4-mapped.
+    pub fn recv_ipv6(&self) -> Ipv6Addr {
+        ipv6(&self.addr_recv_socket_address)
+    }
+
+    /// Returns the IPv4 address of the transmitting node, if it has one.
+    pub fn trans_ipv4(&self) -> Option<Ipv4Addr> {
+        ipv4(&self.addr_trans_socket_address)
+    }
+
+    /// Returns the address of the transmitting node as IPv6, as sent on the wire. IPv4 addresses are IPv4-mapped.
+    pub fn trans_ipv6(&self) -> Ipv6Addr {
+        ipv6(&self.addr_trans_socket_address)
+    }
+
+    /// Checks the timestamp of a [`VersionData`] about to be sent, as peers may penalize garbage ones.
+    ///
+    /// Fails with [`BitcoinMessageError::TimestampOutOfRange`] if the timestamp is negative, or more than
+    /// [`MAX_TIMESTAMP_LEAD`] seconds after `now` (in seconds since the epoch). Received messages are not checked
+    /// when decoding, see [`VersionData::validate`] instead.
+    pub fn checked_timestamp(self, now: i64) -> Result<Self, BitcoinMessageError> {
+        if self.timestamp < 0 || self.timestamp > now.saturating_add(MAX_TIMESTAMP_LEAD) {
+            return Err(BitcoinMessageError::TimestampOutOfRange(self.timestamp));
+        }
+
+        Ok(self)
+    }
+
+    /// Checks the fields of a received [`VersionData`] for nonsensical values, which decoding doesn't reject.
+    ///
+    /// Fails with [`BitcoinMessageError::TimestampOutOfRange`] if the timestamp is more than `max_time_offset`
+    /// seconds away from `now` (both in seconds since the epoch, see [`MAX_TIME_OFFSET`]), and with
+    /// [`BitcoinMessageError::InvalidStartHeight`] if the start height is negative.
+    pub fn validate(&self, now: i64, max_time_offset: u64) -> Result<(), BitcoinMessageError> {
+        if self.timestamp.abs_diff(now) > max_time_offset {
+            return Err(BitcoinMessageError::TimestampOutOfRange(self.timestamp));
+        }
+        if self.start_height < 0 {
+            return Err(BitcoinMessageError::InvalidStartHeight(self.start_height));
+        }
+
+        Ok(())
+    }
+}
+
+/// `sendcmpct` message payload: negotiates compact block relay. See [BIP 152](https://github.com/bitcoin/bips/blob/master/bip-0152.mediawiki).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct SendCmpct {
+    /// Whether new blocks should be announced with `cmpctblock` messages, instead of `inv` or `headers`.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    announce: bool,
+
+    /// The compact blocks protocol version.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    version: u64,
+}
+
+#[cfg(not(feature = "getset"))]
+impl SendCmpct {
+    /// Whether new blocks should be announced with `cmpctblock` messages, instead of `inv` or `headers`.
+    #[inline(always)]
+    pub fn announce(&self) -> &bool {
+        &self.announce
+    }
+
+    /// The compact blocks protocol version.
+    #[inline(always)]
+    pub fn version(&self) -> &u64 {
+        &self.version
+    }
+}
+
+impl SendCmpct {
+    /// Creates new [`SendCmpct`].
+    pub fn new(announce: bool, version: u64) -> Self {
+        Self { announce, version }
+    }
+}
+
+impl BitcoinSerialize for SendCmpct {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(9);
+        buf.write_u8(self.announce.into())?;
+        buf.write_u64::<LittleEndian>(self.version)?;
+
+        Ok(buf)
+    }
+}
+
+impl SendCmpct {
+    /// Deserializes [`SendCmpct`], rejecting an `announce` byte other than `0x00`/`0x01` with `strict`.
+    pub(crate) fn decode(data: &mut impl Read, strict: bool) -> Result<Self, BitcoinMessageError> {
+        let announce = decode_bool(data.read_u8()?, "announce", strict)?;
+        let version = data.read_u64::<LittleEndian>()?;
+
+        Ok(Self { announce, version })
+    }
+}
+
+impl BitcoinDeserialize for SendCmpct {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
     {
+        Self::decode(data, false)
+    }
+}
+
+/// Decodes the boolean `field` from `value`. Any non-zero byte is `true`, unless `strict` only accepts `0x01`.
+fn decode_bool(value: u8, field: &'static str, strict: bool) -> Result<bool, BitcoinMessageError> {
+    match value {
+        0x00 => Ok(false),
+        0x01 => Ok(true),
+        value if strict => Err(BitcoinMessageError::InvalidBooleanByte { field, value }),
+        _ => Ok(true),
+    }
+}
+
+/// Returns `true` for the Unicode format (`Cf`) characters, which are invisible but can change how the text around
+/// them is displayed.
+const fn is_format(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}'
+            | '\u{600}'..='\u{605}'
+            | '\u{61c}'
+            | '\u{6dd}'
+            | '\u{70f}'
+            | '\u{890}'..='\u{891}'
+            | '\u{8e2}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206f}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+            | '\u{110bd}'
+            | '\u{110cd}'
+            | '\u{13430}'..='\u{1343f}'
+            | '\u{1bca0}'..='\u{1bca3}'
+            | '\u{1d173}'..='\u{1d17a}'
+            | '\u{e0001}'
+            | '\u{e0020}'..='\u{e007f}'
+    )
+}
+
+fn ipv4(addr: &SocketAddr) -> Option<Ipv4Addr> {
+    match addr.ip() {
+        IpAddr::V4(x) => Some(x),
+        IpAddr::V6(x) => x.to_ipv4_mapped(),
+    }
+}
+
+pub(crate) fn ipv6(addr: &SocketAddr) -> Ipv6Addr {
+    match addr.ip() {
+        IpAddr::V4(x) => x.to_ipv6_mapped(),
+        IpAddr::V6(x) => x,
+    }
+}
+
+impl BitcoinSerialize for VersionData {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(self.serialized_len());
+        buf.write_i32::<LittleEndian>(self.version)?;
+        buf.write_u64::<LittleEndian>(self.services.bits())?;
+        buf.write_i64::<LittleEndian>(self.timestamp)?;
+        buf.write_u64::<LittleEndian>(self.addr_recv_services.bits())?;
+        buf.write_all(&self.recv_ipv6().octets())?;
+        buf.write_u16::<BigEndian>(self.addr_recv_socket_address.port())?;
+        buf.write_u64::<LittleEndian>(self.addr_trans_services.bits())?;
+        buf.write_all(&self.trans_ipv6().octets())?;
+        buf.write_u16::<BigEndian>(self.addr_trans_socket_address.port())?;
+        buf.write_u64::<LittleEndian>(self.nonce)?;
+        write_var_bytes(&mut buf, self.user_agent.as_bytes())?;
+        buf.write_i32::<LittleEndian>(self.start_height)?;
+        if self.version >= RELAY_MIN_VERSION {
+            buf.write_u8(self.relay.into())?;
+        }
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for VersionData {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        Self::decode(data, false)
+    }
+}
+
+impl VersionData {
+    /// Deserializes [`VersionData`], rejecting a `relay` byte other than `0x00`/`0x01` with `strict`.
+    pub(crate) fn decode(data: &mut impl Read, strict: bool) -> Result<Self, BitcoinMessageError> {
         let version = data.read_i32::<LittleEndian>()?;
         tracing::trace!("Deserialing version `{}`", version);
-        let services = ServiceIdentifier::from_bits_truncate(data.read_u64::<LittleEndian>()?);
+        let services = ServiceIdentifier::from_bits_retain(data.read_u64::<LittleEndian>()?);
         let timestamp = data.read_i64::<LittleEndian>()?;
         let addr_recv_services =
-            ServiceIdentifier::from_bits_truncate(data.read_u64::<LittleEndian>()?);
+            ServiceIdentifier::from_bits_retain(data.read_u64::<LittleEndian>()?);
         let recv_ip: Ipv6Addr = data.read_u128::<BigEndian>()?.into();
         let recv_port = data.read_u16::<BigEndian>()?;
-        let addr_recv_socket_address: SocketAddr = (recv_ip, recv_port).into();
+        // IPv4-mapped addresses are decoded as IPv4
+        let addr_recv_socket_address: SocketAddr = (recv_ip.to_canonical(), recv_port).into();
         let addr_trans_services =
-            ServiceIdentifier::from_bits_truncate(data.read_u64::<LittleEndian>()?);
+            ServiceIdentifier::from_bits_retain(data.read_u64::<LittleEndian>()?);
         let trans_ip: Ipv6Addr = data.read_u128::<BigEndian>()?.into();
         let trans_port = data.read_u16::<BigEndian>()?;
-        let addr_trans_socket_address: SocketAddr = (trans_ip, trans_port).into();
+        // IPv4-mapped addresses are decoded as IPv4
+        let addr_trans_socket_address: SocketAddr = (trans_ip.to_canonical(), trans_port).into();
         let nonce = data.read_u64::<LittleEndian>()?;
-        let user_agent_len = data.read_u8()?;
-        let mut user_agent_bytes = vec![0u8; user_agent_len as usize];
-        data.read_exact(&mut user_agent_bytes)?;
-        let user_agent = String::from_utf8(user_agent_bytes)?;
+        let user_agent = String::from_utf8(read_var_bytes(data)?)?;
         let start_height = data.read_i32::<LittleEndian>()?;
-        let relay: bool = data.read_u8()? != 0x00;
+        let relay = if version >= RELAY_MIN_VERSION {
+            let mut relay = [0u8; 1];
+            match data.read(&mut relay)? {
+                0 => true,
+                _ => decode_bool(relay[0], "relay", strict)?,
+            }
+        } else {
+            true
+        };
 
         Ok(Self {
             version,
@@ -323,16 +1212,16 @@ impl BitcoinDeserialize for VersionData {
 
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::{
+        enums::tests::ALL_COMMANDS,
+        utils::{arbitrary_wire_addr, roundtrip_test},
+    };
+    use alloc::{boxed::Box, string::ToString};
     use hex_literal::hex;
     use quickcheck::{Arbitrary, TestResult};
     use quickcheck_macros::quickcheck;
-    use std::{
-        io::Cursor,
-        net::{IpAddr, Ipv4Addr},
-        time::SystemTime,
-    };
 
     impl Arbitrary for VersionData {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
@@ -343,26 +1232,85 @@ mod tests {
                 }
             };
             let services = ServiceIdentifier::arbitrary(g);
-            Self::new(
+            Self::with_nonce(
+                u64::arbitrary(g),
                 services,
                 i64::arbitrary(g),
                 services,
-                SocketAddr::arbitrary(g),
+                arbitrary_wire_addr(g),
                 services,
-                SocketAddr::arbitrary(g),
+                arbitrary_wire_addr(g),
                 user_agent,
                 i32::arbitrary(g),
                 bool::arbitrary(g),
             )
         }
+
+        /// Zeroes one field at a time, then shrinks the user agent.
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let mut simpler = Vec::new();
+            let mut push_if_changed = |x: Self| {
+                if x != *self {
+                    simpler.push(x);
+                }
+            };
+            push_if_changed(Self {
+                services: ServiceIdentifier::UNNAMED,
+                addr_recv_services: ServiceIdentifier::UNNAMED,
+                addr_trans_services: ServiceIdentifier::UNNAMED,
+                ..self.clone()
+            });
+            push_if_changed(Self {
+                timestamp: 0,
+                ..self.clone()
+            });
+            push_if_changed(Self {
+                addr_recv_socket_address: UNSPECIFIED_SOCKET_ADDRESS,
+                addr_trans_socket_address: UNSPECIFIED_SOCKET_ADDRESS,
+                ..self.clone()
+            });
+            push_if_changed(Self {
+                nonce: 0,
+                ..self.clone()
+            });
+            push_if_changed(Self {
+                start_height: 0,
+                relay: false,
+                ..self.clone()
+            });
+            let this = self.clone();
+            let user_agents = self.user_agent.shrink().map(move |user_agent| Self {
+                user_agent,
+                ..this.clone()
+            });
+
+            Box::new(simpler.into_iter().chain(user_agents))
+        }
     }
 
     impl Arbitrary for Message {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
             let command = Command::arbitrary(g);
             let payload = match command {
+                c if c.has_empty_payload() => Payload::Empty,
                 Command::Version => Payload::Version(VersionData::arbitrary(g)),
-                Command::VerAck => Payload::Empty,
+                Command::Inv => Payload::Inv(Vec::arbitrary(g)),
+                Command::GetData => Payload::GetData(Vec::arbitrary(g)),
+                Command::NotFound => Payload::NotFound(Vec::arbitrary(g)),
+                Command::Block => Payload::Block(Block::arbitrary(g)),
+                Command::Ping => Payload::Ping(u64::arbitrary(g)),
+                Command::Pong => Payload::Pong(u64::arbitrary(g)),
+                Command::GetCfCheckpt => Payload::GetCfCheckpt(GetCfCheckpt::arbitrary(g)),
+                Command::CfCheckpt => Payload::CfCheckpt(CfCheckpt::arbitrary(g)),
+                Command::GetHeaders => Payload::GetHeaders(GetHeaders::arbitrary(g)),
+                Command::Headers => Payload::Headers(Vec::arbitrary(g)),
+                Command::SendCmpct => {
+                    Payload::SendCmpct(SendCmpct::new(bool::arbitrary(g), u64::arbitrary(g)))
+                }
+                Command::FeeFilter => Payload::FeeFilter(u64::arbitrary(g)),
+                Command::Addr => Payload::Addr(Vec::arbitrary(g)),
+                Command::Reject => Payload::Reject(RejectData::arbitrary(g)),
+                c => unreachable!("no payload for `{}`", c),
             };
 
             Self::new(
@@ -376,11 +1324,66 @@ mod tests {
                 payload,
             )
         }
+
+        /// Tries a bare `verack` first, then the mainnet magic, then shrinks the payload.
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let mut simpler = Vec::new();
+            if self.command != Command::VerAck {
+                simpler.push(Self::new(
+                    self.start_string,
+                    Command::VerAck,
+                    Payload::Empty,
+                ));
+            }
+            if self.start_string != START_STRING_MAINNET {
+                simpler.push(Self {
+                    start_string: START_STRING_MAINNET,
+                    ..self.clone()
+                });
+            }
+            let (start_string, command) = (self.start_string, self.command);
+            let payloads: Box<dyn Iterator<Item = Payload>> = match &self.payload {
+                Payload::Version(x) => Box::new(x.shrink().map(Payload::Version)),
+                Payload::Inv(x) => Box::new(x.shrink().map(Payload::Inv)),
+                Payload::GetData(x) => Box::new(x.shrink().map(Payload::GetData)),
+                Payload::NotFound(x) => Box::new(x.shrink().map(Payload::NotFound)),
+                Payload::Headers(x) => Box::new(x.shrink().map(Payload::Headers)),
+                Payload::Addr(x) => Box::new(x.shrink().map(Payload::Addr)),
+                Payload::Ping(x) => Box::new(x.shrink().map(Payload::Ping)),
+                Payload::Pong(x) => Box::new(x.shrink().map(Payload::Pong)),
+                Payload::FeeFilter(x) => Box::new(x.shrink().map(Payload::FeeFilter)),
+                _ => Box::new(core::iter::empty()),
+            };
+            let messages = payloads.map(move |payload| Self::new(start_string, command, payload));
+
+            Box::new(simpler.into_iter().chain(messages))
+        }
     }
 
+    #[test]
+    fn shrinking_simplifies() {
+        let version = version_data_with_user_agent("/Satoshi:25.0.0/");
+        let message = Message::new(
+            [0x01, 0x02, 0x03, 0x04],
+            Command::Version,
+            Payload::Version(version.clone()),
+        );
+
+        assert!(version.shrink().any(|x| x.user_agent().is_empty()));
+        assert!(version.shrink().all(|x| x != version));
+        assert!(message
+            .shrink()
+            .any(|m| *m.command() == Command::VerAck && *m.payload() == Payload::Empty));
+        assert!(message
+            .shrink()
+            .all(|m| m.payload().matches_command(m.command())));
+    }
+
+    roundtrip_test!(version_data_roundtrip, VersionData);
+
     #[quickcheck]
     fn bytes_to_message_fuzz(data: Vec<u8>) {
-        let mut c = Cursor::new(data);
+        let mut c = data.as_slice();
         let _ = Message::from_bytes(&mut c);
     }
 
@@ -399,30 +1402,750 @@ mod tests {
         let _ = x.to_bytes().unwrap();
     }
 
+    #[test]
+    fn version_data_with_nonce_survives_roundtrip() {
+        let x = version_data(0x0123456789abcdef);
+
+        let bytes = x.to_bytes().unwrap();
+        let y = VersionData::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(*x.nonce(), 0x0123456789abcdef);
+        assert_eq!(y.nonce(), x.nonce());
+    }
+
+    #[test]
+    fn version_data_address_accessors_survive_roundtrip() {
+        let v4 = Ipv4Addr::new(192, 168, 1, 2);
+        let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let x = VersionData {
+            addr_recv_socket_address: (v4, 8333).into(),
+            addr_trans_socket_address: (v6, 8333).into(),
+            ..version_data(0)
+        };
+
+        let bytes = x.to_bytes().unwrap();
+        let y = VersionData::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        assert!(y.addr_recv_socket_address().is_ipv4());
+        assert_eq!(y.recv_ipv4(), Some(v4));
+        assert_eq!(y.recv_ipv6(), v4.to_ipv6_mapped());
+        assert!(y.addr_trans_socket_address().is_ipv6());
+        assert_eq!(y.trans_ipv4(), None);
+        assert_eq!(y.trans_ipv6(), v6);
+    }
+
+    #[test]
+    fn checked_timestamp_bounds() {
+        let now = 1_700_000_000;
+        let mut x = VersionData::arbitrary(&mut quickcheck::Gen::new(100));
+        for timestamp in [0, now, now + MAX_TIMESTAMP_LEAD] {
+            x.timestamp = timestamp;
+            assert!(x.clone().checked_timestamp(now).is_ok(), "{}", timestamp);
+        }
+        for timestamp in [-1, i64::MIN, now + MAX_TIMESTAMP_LEAD + 1, i64::MAX] {
+            x.timestamp = timestamp;
+            assert!(
+                matches!(
+                    x.clone().checked_timestamp(now),
+                    Err(BitcoinMessageError::TimestampOutOfRange(t)) if t == timestamp
+                ),
+                "{}",
+                timestamp
+            );
+        }
+    }
+
+    #[test]
+    fn version_data_validation() {
+        let now = 1_700_000_000;
+        let mut x = VersionData::arbitrary(&mut quickcheck::Gen::new(100));
+        x.timestamp = now + MAX_TIME_OFFSET as i64;
+        x.start_height = 0;
+        assert!(x.validate(now, MAX_TIME_OFFSET).is_ok());
+
+        x.timestamp = now - MAX_TIME_OFFSET as i64 - 1;
+        assert!(matches!(
+            x.validate(now, MAX_TIME_OFFSET),
+            Err(BitcoinMessageError::TimestampOutOfRange(t)) if t == x.timestamp
+        ));
+        assert!(x.validate(now, MAX_TIME_OFFSET + 1).is_ok());
+
+        x.timestamp = now;
+        x.start_height = -1;
+        assert!(matches!(
+            x.validate(now, MAX_TIME_OFFSET),
+            Err(BitcoinMessageError::InvalidStartHeight(-1))
+        ));
+    }
+
+    #[test]
+    fn version_data_relay_defaults_to_true_when_absent() {
+        let mut x = VersionData::arbitrary(&mut quickcheck::Gen::new(100));
+        x.relay = false;
+        let mut bytes = x.to_bytes().unwrap();
+        bytes.pop();
+
+        let y = VersionData::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        assert!(*y.relay());
+    }
+
+    /// [`VersionData`] with the given `nonce`, for tests which don't care about the other fields.
+    pub(crate) fn version_data(nonce: u64) -> VersionData {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 8333));
+        VersionData::with_nonce(
+            nonce,
+            ServiceIdentifier::NODE_NETWORK,
+            0,
+            ServiceIdentifier::NODE_NETWORK,
+            addr,
+            ServiceIdentifier::NODE_NETWORK,
+            addr,
+            "".to_string(),
+            0,
+            true,
+        )
+    }
+
+    fn version_data_with_user_agent(user_agent: &str) -> VersionData {
+        VersionData {
+            user_agent: user_agent.to_string(),
+            ..version_data(0)
+        }
+    }
+
+    #[test]
+    fn payload_display_is_one_line() {
+        let version = VersionData {
+            services: ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS,
+            ..version_data_with_user_agent("/Satoshi:25.0.0/")
+        };
+
+        assert_eq!(Payload::Empty.to_string(), "Empty");
+        assert_eq!(
+            Payload::Version(version).to_string(),
+            r#"Version(v=70015, ua="/Satoshi:25.0.0/", services=NODE_NETWORK | NODE_WITNESS)"#
+        );
+        assert_eq!(Payload::Ping(42).to_string(), "Ping(nonce=42)");
+        assert_eq!(Payload::Headers(vec![]).to_string(), "Headers(n=0)");
+    }
+
+    #[test]
+    fn payload_as_version() {
+        let x = version_data(7);
+
+        assert_eq!(Payload::Version(x.clone()).as_version(), Some(&x));
+        assert_eq!(Payload::Empty.as_version(), None);
+        assert_eq!(Payload::Ping(7).as_version(), None);
+    }
+
+    #[test]
+    fn version_data_without_services_roundtrip() {
+        let x = VersionData {
+            services: ServiceIdentifier::UNNAMED,
+            addr_recv_services: ServiceIdentifier::UNNAMED,
+            addr_trans_services: ServiceIdentifier::UNNAMED,
+            ..version_data(0)
+        };
+        let bytes = x.to_bytes().unwrap();
+
+        assert_eq!(bytes[4..12], [0u8; 8]);
+        let y = VersionData::from_bytes(&mut bytes.as_slice()).unwrap();
+        assert_eq!(y, x);
+        assert!(y.services().is_empty());
+    }
+
+    #[test]
+    fn services_survive_version_roundtrip() {
+        let services = ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS;
+        let recv_services = ServiceIdentifier::NODE_BLOOM;
+        let trans_services =
+            ServiceIdentifier::NODE_NETWORK_LIMITED | ServiceIdentifier::NODE_GETUTXO;
+        let x = VersionData {
+            services,
+            addr_recv_services: recv_services,
+            addr_trans_services: trans_services,
+            ..version_data(0)
+        };
+        let message = Message::new(START_STRING_MAINNET, Command::Version, Payload::Version(x));
+        let mut bytes = message.to_bytes().unwrap();
+
+        let Payload::Version(y) = Message::from_bytes(&mut bytes.as_slice()).unwrap().payload
+        else {
+            panic!("expected version payload");
+        };
+        assert_eq!(*y.services(), services);
+        assert_eq!(*y.addr_recv_services(), recv_services);
+        assert_eq!(*y.addr_trans_services(), trans_services);
+
+        // bits this crate doesn't know are kept when decoding
+        let offset = HEADER_SIZE + 4;
+        bytes[offset..offset + 8].copy_from_slice(&(services.bits() | 0x80000).to_le_bytes());
+        let payload = Payload::from_bytes(&mut &bytes[HEADER_SIZE..], &Command::Version).unwrap();
+        let Payload::Version(y) = payload else {
+            panic!("expected version payload");
+        };
+        assert_eq!(y.services().bits(), services.bits() | 0x80000);
+        assert_eq!(y.services().known_bits(), services);
+        assert_eq!(y.services().unknown_bits(), 0x80000);
+        assert_eq!(y.to_bytes().unwrap(), bytes[HEADER_SIZE..]);
+    }
+
+    #[test]
+    fn unspecified_trans_address_is_all_zeros() {
+        let x = VersionData {
+            addr_trans_socket_address: UNSPECIFIED_SOCKET_ADDRESS,
+            ..version_data(0)
+        };
+        let bytes = x.to_bytes().unwrap();
+
+        // version, services, timestamp, addr_recv, addr_trans services
+        let trans = 4 + 8 + 8 + 26 + 8;
+        assert_eq!(bytes[trans..trans + 18], [0u8; 18]);
+        let y = VersionData::from_bytes(&mut bytes.as_slice()).unwrap();
+        assert_eq!(*y.addr_trans_socket_address(), UNSPECIFIED_SOCKET_ADDRESS);
+    }
+
+    #[test]
+    fn user_agent_sanitized_strips_control_characters() {
+        let x = version_data_with_user_agent("/Satoshi:25.0.0/\x1b[2J\r\n\0evil/");
+
+        assert_eq!(x.user_agent_sanitized(), "/Satoshi:25.0.0/[2Jevil/");
+        assert_eq!(x.user_agent(), "/Satoshi:25.0.0/\x1b[2J\r\n\0evil/");
+
+        let x = version_data_with_user_agent("/Satoshi:25.0.0/\u{202e}live\u{2066}\u{200b}/");
+        assert_eq!(x.user_agent_sanitized(), "/Satoshi:25.0.0/live/");
+    }
+
+    #[test]
+    fn user_agent_sanitized_truncates() {
+        let short = "a".repeat(USER_AGENT_DISPLAY_LEN);
+        let long = "a".repeat(MAX_USER_AGENT_LEN);
+
+        assert_eq!(
+            version_data_with_user_agent(&short).user_agent_sanitized(),
+            short
+        );
+        let sanitized = version_data_with_user_agent(&long).user_agent_sanitized();
+        assert_eq!(sanitized.chars().count(), USER_AGENT_DISPLAY_LEN);
+        assert!(sanitized.ends_with('…'));
+    }
+
+    #[test]
+    fn version_data_with_empty_user_agent_is_86_bytes() {
+        let x = version_data(0);
+
+        // version, services, timestamp, 2 * (services, ip, port), nonce, user agent length, start height, relay
+        assert_eq!(4 + 8 + 8 + 2 * (8 + 16 + 2) + 8 + 1 + 4 + 1, 86);
+        assert_eq!(x.to_bytes().unwrap().len(), 86);
+        assert_eq!(x.serialized_len(), 86);
+    }
+
+    #[quickcheck]
+    fn version_data_advertised_version_survives_roundtrip(x: VersionData, version: i32) -> bool {
+        let x = x.with_advertised_version(version);
+        let bytes = x.to_bytes().unwrap();
+
+        bytes.len() == x.serialized_len()
+            && *VersionData::from_bytes(&mut bytes.as_slice())
+                .unwrap()
+                .version()
+                == version
+    }
+
+    #[test]
+    fn version_data_relay_is_omitted_before_relay_min_version() {
+        let mut x = VersionData::arbitrary(&mut quickcheck::Gen::new(100));
+        x.relay = false;
+        let with_relay = x.to_bytes().unwrap();
+        x.version = RELAY_MIN_VERSION - 1;
+        let without_relay = x.to_bytes().unwrap();
+
+        let y = VersionData::from_bytes(&mut without_relay.as_slice()).unwrap();
+
+        assert_eq!(without_relay.len(), with_relay.len() - 1);
+        assert!(*y.relay());
+    }
+
     #[test]
     fn deserialization_checks_checksum() {
         // varack with invalid checksum:
-        let mut data = Cursor::new(hex!("f9beb4d976657261636b000000000000000000005df6e0e1"));
+        let mut data = hex!("f9beb4d976657261636b000000000000000000005df6e0e1").as_slice();
 
         let result = Message::from_bytes(&mut data);
 
         assert!(matches!(result, Err(BitcoinMessageError::ChecksumMismatch)));
     }
 
+    #[test]
+    fn overlong_version_payload_is_rejected() {
+        let mut data = hex!("f9beb4d976657273696f6e0000000000d007000000000000").to_vec();
+        data.extend([0u8; 2000]);
+
+        let result = Message::from_bytes(&mut data.as_slice());
+
+        assert!(matches!(
+            result,
+            Err(BitcoinMessageError::PayloadSizeUnexpected {
+                command: Command::Version,
+                len: 2000,
+                max: MAX_SMALL_PAYLOAD_SIZE
+            })
+        ));
+    }
+
+    #[test]
+    fn longest_version_payload_is_plausible() {
+        let version = VersionData {
+            services: ServiceIdentifier::all(),
+            addr_recv_services: ServiceIdentifier::all(),
+            addr_recv_socket_address: UNSPECIFIED_SOCKET_ADDRESS,
+            addr_trans_services: ServiceIdentifier::all(),
+            addr_trans_socket_address: UNSPECIFIED_SOCKET_ADDRESS,
+            ..version_data_with_user_agent(&"x".repeat(MAX_USER_AGENT_LEN))
+        };
+        let bytes = Message::new(
+            Network::Mainnet.magic(),
+            Command::Version,
+            Payload::Version(version),
+        )
+        .to_bytes()
+        .unwrap();
+
+        assert!(bytes.len() - HEADER_SIZE <= Command::Version.max_payload_len());
+        assert!(Message::from_bytes(&mut bytes.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn negotiated_version_is_the_minimum() {
+        assert_eq!(negotiated_version(70015, 70016), 70015);
+        assert_eq!(negotiated_version(70016, 70015), 70015);
+        assert_eq!(negotiated_version(70015, 70015), 70015);
+        assert_eq!(negotiated_version(PROTOCOL_VERSION, 60001), 60001);
+        assert!(negotiated_version(PROTOCOL_VERSION, 70016) < ADDRV2_MIN_VERSION);
+    }
+
+    #[test]
+    fn ping_serialization() {
+        let ping = Message::new(
+            START_STRING_MAINNET,
+            Command::Ping,
+            Payload::Ping(0x0123456789abcdef),
+        );
+
+        let bytes = ping.to_bytes().unwrap();
+        let decoded = Message::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(&bytes[4..16], b"ping\0\0\0\0\0\0\0\0");
+        assert_eq!(bytes[24..], hex!("efcdab8967452301"));
+        assert!(matches!(
+            decoded.payload(),
+            Payload::Ping(0x0123456789abcdef)
+        ));
+    }
+
     #[test]
     fn verack_deserialization() {
         // varack:
-        let mut data = Cursor::new(hex!("f9beb4d976657261636b000000000000000000005df6e0e2"));
+        let mut data = hex!("f9beb4d976657261636b000000000000000000005df6e0e2").as_slice();
 
         let result = Message::from_bytes(&mut data);
 
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn strict_decoding_rejects_trailing_payload_bytes() {
+        let x = VersionData::arbitrary(&mut quickcheck::Gen::new(100));
+        let mut payload = x.to_bytes().unwrap();
+        payload.extend([0xaa, 0xbb, 0xcc]);
+        let mut data = hex!("f9beb4d976657273696f6e0000000000").to_vec();
+        data.extend((payload.len() as u32).to_le_bytes());
+        data.extend(checksum(&payload));
+        data.extend(&payload);
+
+        let lenient = Message::from_bytes(&mut data.as_slice());
+        let strict = Message::from_bytes_strict(&mut data.as_slice());
+
+        assert!(lenient.is_ok());
+        assert!(matches!(
+            strict,
+            Err(BitcoinMessageError::TrailingPayloadBytes { extra: 3 })
+        ));
+    }
+
+    fn raw_message(command_name: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut data = START_STRING_MAINNET.to_vec();
+        data.extend(command_name);
+        data.resize(16, 0);
+        data.extend((payload.len() as u32).to_le_bytes());
+        data.extend(checksum(payload));
+        data.extend(payload);
+
+        data
+    }
+
+    #[test]
+    fn strict_decoding_rejects_invalid_relay_byte() {
+        let mut x = VersionData::arbitrary(&mut quickcheck::Gen::new(100));
+        x.version = PROTOCOL_VERSION;
+        let mut payload = x.to_bytes().unwrap();
+        *payload.last_mut().unwrap() = 0x02;
+        let data = raw_message(b"version", &payload);
+
+        let lenient = Message::from_bytes(&mut data.as_slice()).unwrap();
+        let strict = Message::from_bytes_strict(&mut data.as_slice());
+
+        assert!(matches!(lenient.payload(), Payload::Version(v) if *v.relay()));
+        assert!(matches!(
+            strict,
+            Err(BitcoinMessageError::InvalidBooleanByte {
+                field: "relay",
+                value: 0x02
+            })
+        ));
+    }
+
+    #[test]
+    fn strict_decoding_rejects_invalid_announce_byte() {
+        let data = raw_message(b"sendcmpct", &hex!("ff0200000000000000"));
+
+        let lenient = Message::from_bytes(&mut data.as_slice()).unwrap();
+        let strict = Message::from_bytes_strict(&mut data.as_slice());
+
+        assert!(matches!(lenient.payload(), Payload::SendCmpct(x) if *x.announce()));
+        assert!(matches!(
+            strict,
+            Err(BitcoinMessageError::InvalidBooleanByte {
+                field: "announce",
+                value: 0xff
+            })
+        ));
+    }
+
+    #[test]
+    fn raw_payload_reencodes_exactly() {
+        // decoding normalizes the announce byte, and drops the trailing bytes
+        let data = raw_message(b"sendcmpct", &hex!("ff0200000000000000abcd"));
+
+        let decoded = Message::from_bytes(&mut data.as_slice()).unwrap();
+        let raw = Message::from_bytes_raw(&mut data.as_slice()).unwrap();
+
+        assert_ne!(decoded.to_bytes().unwrap(), data);
+        assert_eq!(*raw.command(), Command::SendCmpct);
+        assert_eq!(
+            *raw.payload(),
+            Payload::Raw(hex!("ff0200000000000000abcd").to_vec())
+        );
+        assert_eq!(raw.to_bytes().unwrap(), data);
+        assert_eq!(raw.serialized_len().unwrap(), data.len());
+    }
+
+    #[test]
+    fn payload_reader_reads_raw_payload() {
+        let data = raw_message(b"sendcmpct", &hex!("010200000000000000"));
+        let raw = Message::from_bytes_raw(&mut data.as_slice()).unwrap();
+
+        let mut reader = raw.payload_reader().unwrap();
+        let announce = reader.read_u8().unwrap();
+        let version = reader.read_u64::<LittleEndian>().unwrap();
+
+        assert_eq!((announce, version), (1, 2));
+        assert!(reader.read_u8().is_err());
+        assert!(Message::from_bytes(&mut data.as_slice())
+            .unwrap()
+            .payload_reader()
+            .is_none());
+    }
+
+    #[test]
+    fn strict_decoding_accepts_valid_boolean_bytes() {
+        for announce in [0x00, 0x01] {
+            let mut payload = vec![announce];
+            payload.extend(2u64.to_le_bytes());
+            let data = raw_message(b"sendcmpct", &payload);
+
+            let message = Message::from_bytes_strict(&mut data.as_slice()).unwrap();
+
+            assert!(
+                matches!(message.payload(), Payload::SendCmpct(x) if *x.announce() == (announce == 0x01))
+            );
+        }
+    }
+
+    #[test]
+    fn empty_input_is_connection_closed() {
+        let truncated = hex!("f9beb4d976657261636b");
+
+        assert!(matches!(
+            Message::from_bytes(&mut [].as_slice()),
+            Err(BitcoinMessageError::ConnectionClosed)
+        ));
+        assert!(matches!(
+            Message::from_bytes(&mut truncated.as_slice()),
+            Err(BitcoinMessageError::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn command_name_ends_at_first_null() {
+        // "ver\0ack\0\0\0\0\0" would be "verack" if all nulls were stripped
+        let data = hex!("f9beb4d97665720061636b0000000000000000005df6e0e2");
+
+        let result = Message::from_bytes(&mut data.as_slice());
+
+        assert!(matches!(
+            result,
+            Err(BitcoinMessageError::MalformedCommandPadding(name)) if name == "ver"
+        ));
+    }
+
+    #[test]
+    fn payload_checksum_of_decoded_verack() {
+        let mut data = hex!("f9beb4d976657261636b000000000000000000005df6e0e2").as_slice();
+
+        let message = Message::from_bytes(&mut data).unwrap();
+
+        assert_eq!(message.payload_checksum().unwrap(), hex!("5df6e0e2"));
+    }
+
+    #[test]
+    fn verify_frame_checks_checksum() {
+        let verack = hex!("f9beb4d976657261636b000000000000000000005df6e0e2");
+        let mut corrupted = raw_message(b"foo", &[0x01, 0x02]);
+        corrupted[HEADER_SIZE] = 0xff;
+
+        assert!(verify_frame(&verack).is_ok());
+        assert!(verify_frame(&raw_message(b"foo", &[0x01, 0x02])).is_ok());
+        assert!(matches!(
+            verify_frame(&corrupted),
+            Err(BitcoinMessageError::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn verify_frame_checks_length() {
+        let frame = raw_message(b"ping", &[0u8; 8]);
+        let mut longer = frame.clone();
+        longer.push(0x00);
+
+        assert!(matches!(
+            verify_frame(&frame[..frame.len() - 3]),
+            Err(BitcoinMessageError::MissingPayloadBytes { missing: 3 })
+        ));
+        assert!(matches!(
+            verify_frame(&longer),
+            Err(BitcoinMessageError::TrailingPayloadBytes { extra: 1 })
+        ));
+    }
+
+    #[test]
+    fn new_checked_rejects_mismatched_payload() {
+        assert!(matches!(
+            Message::new_checked(START_STRING_MAINNET, Command::Version, Payload::Empty),
+            Err(BitcoinMessageError::PayloadMismatch(Command::Version))
+        ));
+        assert!(matches!(
+            Message::new_checked(START_STRING_MAINNET, Command::Ping, Payload::Pong(1)),
+            Err(BitcoinMessageError::PayloadMismatch(Command::Ping))
+        ));
+        assert!(
+            Message::new_checked(START_STRING_MAINNET, Command::SendHeaders, Payload::Empty)
+                .is_ok()
+        );
+    }
+
+    #[quickcheck]
+    fn decoded_payload_matches_command(m: Message) -> bool {
+        let bytes = m.to_bytes().unwrap();
+        let decoded = Message::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        decoded.payload().matches_command(decoded.command())
+    }
+
+    #[test]
+    fn getdata_batches_stay_within_limit() {
+        let item = InventoryVector::new(crate::inventory::InventoryType::Tx, Default::default());
+
+        let batches = Message::getdata_batches(vec![item; MAX_INVENTORY_LEN + 1], Network::Testnet);
+
+        let counts: Vec<usize> = batches
+            .iter()
+            .map(|m| match m.payload() {
+                Payload::GetData(items) => items.len(),
+                p => panic!("expected getdata, got {:?}", p),
+            })
+            .collect();
+        assert_eq!(counts, vec![MAX_INVENTORY_LEN, 1]);
+        assert!(batches
+            .iter()
+            .all(|m| m.network() == Some(Network::Testnet)));
+        assert!(batches.iter().all(|m| m.to_bytes().is_ok()));
+        assert!(Message::getdata_batches(vec![], Network::Mainnet).is_empty());
+    }
+
+    #[test]
+    fn command_str_is_the_wire_name() {
+        let message = Message::new(Network::Mainnet.magic(), Command::Ping, Payload::Ping(42));
+
+        assert!(matches!(message.command_str(), Cow::Borrowed("ping")));
+    }
+
+    #[test]
+    fn clone_with_network_changes_only_start_string() {
+        let message = Message::new(Network::Mainnet.magic(), Command::Ping, Payload::Ping(42));
+
+        let retargeted = message.clone_with_network(Network::Regtest);
+
+        let (original, bytes) = (message.to_bytes().unwrap(), retargeted.to_bytes().unwrap());
+        assert_eq!(retargeted.network(), Some(Network::Regtest));
+        assert_eq!(original[..4], Network::Mainnet.magic());
+        assert_eq!(bytes[..4], Network::Regtest.magic());
+        assert_eq!(original[4..], bytes[4..]);
+    }
+
+    #[test]
+    fn custom_network_magic_roundtrip() {
+        let network = Network::Custom {
+            magic: [0x01, 0x02, 0x03, 0x04],
+            port: 28333,
+        };
+        let bytes = Message::new(network.magic(), Command::VerAck, Payload::Empty)
+            .to_bytes()
+            .unwrap();
+
+        let message = Message::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(*message.start_string(), network.magic());
+        assert_eq!(message.network(), None);
+        assert_eq!(network.default_port(), 28333);
+    }
+
+    #[test]
+    fn empty_lists_roundtrip() {
+        let payloads = [
+            (Command::Inv, Payload::Inv(vec![])),
+            (Command::GetData, Payload::GetData(vec![])),
+            (Command::NotFound, Payload::NotFound(vec![])),
+            (Command::Headers, Payload::Headers(vec![])),
+            (Command::Addr, Payload::Addr(vec![])),
+        ];
+
+        for (command, payload) in payloads {
+            let bytes = Message::new(START_STRING_MAINNET, command, payload.clone())
+                .to_bytes()
+                .unwrap();
+            let message = Message::from_bytes_strict(&mut bytes.as_slice()).unwrap();
+
+            // just the zero count
+            assert_eq!(bytes[HEADER_SIZE..], [0x00], "{}", command);
+            assert_eq!(*message.payload(), payload, "{}", command);
+        }
+    }
+
+    #[test]
+    fn commands_are_ordered_by_declaration() {
+        let mut sorted = ALL_COMMANDS;
+        sorted.reverse();
+        sorted.sort();
+
+        assert_eq!(sorted, ALL_COMMANDS);
+        assert!(Command::Version < Command::VerAck);
+        assert!(Command::GetAddr < Command::Reject);
+    }
+
+    #[test]
+    fn command_as_str_matches_display() {
+        for command in ALL_COMMANDS {
+            assert_eq!(command.as_str(), command.to_string());
+            assert_eq!(Command::try_from(command.as_str()).unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn empty_payload_commands_decode_empty() {
+        for command in ALL_COMMANDS {
+            let decoded = Payload::from_bytes(&mut [].as_slice(), &command);
+
+            assert_eq!(
+                matches!(decoded, Ok(Payload::Empty)),
+                command.has_empty_payload(),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn only_verack_completes_handshake() {
+        let commands = ALL_COMMANDS;
+
+        for command in commands {
+            // the payload doesn't matter
+            let message = Message {
+                start_string: START_STRING_MAINNET,
+                command,
+                payload: Payload::Empty,
+            };
+
+            assert_eq!(
+                message.is_handshake_complete_trigger(),
+                command == Command::VerAck,
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[quickcheck]
+    fn serialized_len_matches_bytes(m: Message) -> bool {
+        m.serialized_len().unwrap() == m.to_bytes().unwrap().len()
+    }
+
+    #[quickcheck]
+    fn payload_byte_len_matches_bytes(m: Message) -> bool {
+        m.payload().byte_len().unwrap() == m.payload().to_bytes().unwrap().len()
+    }
+
+    #[quickcheck]
+    fn payload_checksum_matches_header(m: Message) -> bool {
+        m.payload_checksum().unwrap() == m.to_bytes().unwrap()[20..24]
+    }
+
+    #[test]
+    fn iter_from_bytes_yields_all_messages_in_order() {
+        let verack = Message::new(START_STRING_MAINNET, Command::VerAck, Payload::Empty);
+        let version = Message::new(
+            START_STRING_MAINNET,
+            Command::Version,
+            Payload::Version(VersionData::arbitrary(&mut quickcheck::Gen::new(100))),
+        );
+        let data = [verack.to_bytes().unwrap(), version.to_bytes().unwrap()].concat();
+
+        let commands = Message::iter_from_bytes(&data)
+            .map(|m| *m.unwrap().command())
+            .collect::<Vec<_>>();
+
+        assert_eq!(commands, vec![Command::VerAck, Command::Version]);
+    }
+
+    #[test]
+    fn iter_from_bytes_stops_after_first_error() {
+        let data = hex!("f9beb4d976657261636b000000000000000000005df6e0e2f9beb4d976657261636b000000000000000000005df6e0e1f9beb4d976657261636b000000000000000000005df6e0e2");
+
+        let results = Message::iter_from_bytes(&data).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(BitcoinMessageError::ChecksumMismatch)
+        ));
     }
 
     #[quickcheck]
     fn empty_payload_has_correct_checksum(m: Message) -> TestResult {
         match m.payload() {
-            Payload::Version(_) => TestResult::discard(),
             Payload::Empty => TestResult::from_bool(
                 m.to_bytes()
                     .unwrap()
@@ -434,12 +2157,16 @@ mod tests {
                     .collect::<Vec<_>>()
                     == hex!("5df6e0e2"),
             ),
+            _ => TestResult::discard(),
         }
     }
 
     #[test]
+    #[cfg(feature = "rand")]
     #[should_panic]
     fn version_data_new_with_user_agent_longer_than_max_length_panics() {
+        use std::time::SystemTime;
+
         let user_agent = (0..MAX_USER_AGENT_LEN + 1).map(|_| 'a').collect::<String>();
         let _ = VersionData::new(
             ServiceIdentifier::NODE_NETWORK,
@@ -0,0 +1,1301 @@
+use crate::{
+    block::{Block, BlockHeader, BLOCK_HEADER_SIZE},
+    enums::{Command, Network},
+    errors::BitcoinMessageError,
+    headers::{GetHeaders, MAX_HEADERS_LEN},
+    inventory::{InventoryType, InventoryVector},
+    message::{
+        negotiated_version, BitcoinDeserialize, BitcoinSerialize, Header, Message, Payload,
+        VersionData, HEADER_SIZE,
+    },
+    pool::{BufferPool, PooledBuffer},
+    reader::ReaderConfig,
+    types::{Hash, VarInt},
+    utils::CHECKSUM_SIZE,
+    PROTOCOL_VERSION,
+};
+#[cfg(feature = "getset")]
+use getset::Getters;
+use sha2::{Digest, Sha256};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    time::{Instant, Interval, MissedTickBehavior},
+};
+
+/// Connection to a Bitcoin node, exchanging [`Message`]s over an async stream.
+#[derive(Debug)]
+pub struct Peer<T> {
+    stream: MessageStream<T>,
+    network: Network,
+    keepalive: Option<Keepalive>,
+    // nonce of the `version` we sent, to detect connecting to ourselves
+    local_nonce: Option<u64>,
+    local_version: Option<i32>,
+    // `version` received from the other side
+    remote_version: Option<VersionData>,
+    // whether each side sent `sendaddrv2` during the handshake
+    local_addrv2: bool,
+    remote_addrv2: bool,
+}
+
+/// Framed [`Message`] stream over an async byte stream, without any of the handshake logic of [`Peer`].
+///
+/// Owns the read buffer, so partially received messages are kept between reads.
+#[derive(Debug)]
+pub struct MessageStream<T> {
+    stream: T,
+    config: ReaderConfig,
+    buffer: PooledBuffer,
+    metrics: PeerMetrics,
+    // payload bytes of a rejected message, still to be discarded
+    skip: usize,
+}
+
+/// Traffic counters of a [`Peer`]. See [`Peer::metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct PeerMetrics {
+    /// Bytes written to the stream.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    bytes_sent: u64,
+
+    /// Bytes read from the stream.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    bytes_received: u64,
+
+    /// Messages sent, including keepalive `ping`s and `pong`s.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    messages_sent: u64,
+
+    /// Messages received, including keepalive `ping`s and `pong`s, and messages that failed to decode.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    messages_received: u64,
+}
+
+#[cfg(not(feature = "getset"))]
+impl PeerMetrics {
+    /// Bytes written to the stream.
+    #[inline(always)]
+    pub fn bytes_sent(&self) -> &u64 {
+        &self.bytes_sent
+    }
+
+    /// Bytes read from the stream.
+    #[inline(always)]
+    pub fn bytes_received(&self) -> &u64 {
+        &self.bytes_received
+    }
+
+    /// Messages sent, including keepalive `ping`s and `pong`s.
+    #[inline(always)]
+    pub fn messages_sent(&self) -> &u64 {
+        &self.messages_sent
+    }
+
+    /// Messages received, including keepalive `ping`s and `pong`s, and messages that failed to decode.
+    #[inline(always)]
+    pub fn messages_received(&self) -> &u64 {
+        &self.messages_received
+    }
+}
+
+/// State of the `ping`/`pong` keepalive. See [`Peer::enable_keepalive`].
+#[derive(Debug)]
+struct Keepalive {
+    interval: Interval,
+    pending_nonce: Option<u64>,
+}
+
+impl Peer<TcpStream> {
+    /// Opens a TCP connection to the node at `addr`.
+    pub async fn connect(addr: SocketAddr, network: Network) -> Result<Self, BitcoinMessageError> {
+        Ok(Self::new(TcpStream::connect(addr).await?, network))
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Peer<T> {
+    /// Creates new [`Peer`] communicating over `stream`, with the default [`ReaderConfig`].
+    pub fn new(stream: T, network: Network) -> Self {
+        Self::with_config(stream, network, ReaderConfig::default())
+    }
+
+    /// Creates new [`Peer`] communicating over `stream`, with the given [`ReaderConfig`].
+    pub fn with_config(stream: T, network: Network, config: ReaderConfig) -> Self {
+        Self::from_stream(MessageStream::with_config(stream, config), network)
+    }
+
+    /// Creates new [`Peer`] communicating over `stream`, with the given [`ReaderConfig`], and its read buffer taken
+    /// from `pool`. The buffer is returned to the pool when the peer is dropped.
+    pub fn with_pool(stream: T, network: Network, config: ReaderConfig, pool: &BufferPool) -> Self {
+        Self::from_stream(MessageStream::with_pool(stream, config, pool), network)
+    }
+
+    /// Creates new [`Peer`] on top of `stream`.
+    pub fn from_stream(stream: MessageStream<T>, network: Network) -> Self {
+        Self {
+            stream,
+            network,
+            keepalive: None,
+            local_nonce: None,
+            local_version: None,
+            remote_version: None,
+            local_addrv2: false,
+            remote_addrv2: false,
+        }
+    }
+
+    /// Returns the [`Network`] of this peer.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Returns the [`ReaderConfig`] used when receiving messages.
+    pub fn config(&self) -> &ReaderConfig {
+        self.stream.config()
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &T {
+        self.stream.get_ref()
+    }
+
+    /// Consumes the peer, returning the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.stream.into_inner()
+    }
+
+    /// Closes the connection gracefully: flushes pending writes and shuts down the write half of the stream, so the
+    /// other side sees a clean end of stream instead of a reset. Bitcoin has no disconnect message.
+    pub async fn disconnect(mut self) -> Result<(), BitcoinMessageError> {
+        self.stream.shutdown().await
+    }
+
+    /// Returns the protocol version in effect on this connection, once both `version` messages were exchanged.
+    /// See [`negotiated_version`].
+    pub fn negotiated_version(&self) -> Option<i32> {
+        Some(negotiated_version(
+            self.local_version?,
+            *self.remote_version.as_ref()?.version(),
+        ))
+    }
+
+    /// Returns the `version` received from the other side, once [`Peer::recv_version`] succeeded.
+    pub fn peer_version(&self) -> Option<&VersionData> {
+        self.remote_version.as_ref()
+    }
+
+    /// Returns a snapshot of the traffic counters of this connection.
+    pub fn metrics(&self) -> PeerMetrics {
+        self.stream.metrics()
+    }
+
+    /// Returns `true` if both sides sent `sendaddrv2` during the handshake, so addresses are to be exchanged with
+    /// `addrv2` instead of `addr`. See [`Peer::send_sendaddrv2`].
+    pub fn addrv2(&self) -> bool {
+        self.local_addrv2 && self.remote_addrv2
+    }
+
+    /// Keeps the connection alive, as nodes disconnect idle peers.
+    ///
+    /// While [`Peer::recv`] is awaited, a `ping` with a fresh nonce is sent every `interval`, and received `ping`s
+    /// are answered with a matching `pong`. Neither `ping` nor `pong` messages are returned by `recv` anymore.
+    /// A `pong` with an unexpected nonce is logged, but otherwise ignored.
+    pub fn enable_keepalive(&mut self, interval: Duration) {
+        let mut interval = tokio::time::interval_at(Instant::now() + interval, interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        self.keepalive = Some(Keepalive {
+            interval,
+            pending_nonce: None,
+        });
+    }
+
+    /// Stops the keepalive enabled by [`Peer::enable_keepalive`].
+    pub fn disable_keepalive(&mut self) {
+        self.keepalive = None;
+    }
+
+    /// Sends a message with the given command and payload, on this peer's [`Network`].
+    pub async fn send(
+        &mut self,
+        command: Command,
+        payload: Payload,
+    ) -> Result<(), BitcoinMessageError> {
+        let message = Message::new(self.network.magic(), command, payload);
+
+        self.send_message(&message).await
+    }
+
+    /// Sends `message`.
+    pub async fn send_message(&mut self, message: &Message) -> Result<(), BitcoinMessageError> {
+        self.stream.send_message(message).await
+    }
+
+    /// Sends all `messages` with a single write. See [`write_messages`].
+    pub async fn send_messages(&mut self, messages: &[Message]) -> Result<(), BitcoinMessageError> {
+        self.stream.send_messages(messages).await
+    }
+
+    /// Sends our `version`. The first step of the handshake.
+    ///
+    /// The handshake consists of four steps, which can be composed in either of two orders:
+    ///
+    /// * outbound, when we initiated the connection: [`Peer::send_version`], [`Peer::recv_version`],
+    ///   [`Peer::send_verack`], [`Peer::recv_verack`];
+    /// * inbound, when the other side did: [`Peer::recv_version`], [`Peer::send_version`], [`Peer::send_verack`],
+    ///   [`Peer::recv_verack`].
+    ///
+    /// Feature negotiation messages, like [`Peer::send_sendaddrv2`], go between `send_version` and `send_verack`.
+    pub async fn send_version(&mut self, version: VersionData) -> Result<(), BitcoinMessageError> {
+        self.local_nonce = Some(*version.nonce());
+        self.local_version = Some(*version.version());
+
+        self.send(Command::Version, Payload::Version(version)).await
+    }
+
+    /// Receives the `version` of the other side. See [`Peer::send_version`] for the handshake steps.
+    ///
+    /// Fails with [`BitcoinMessageError::Rejected`] if the other side rejected our `version`, with
+    /// [`BitcoinMessageError::UnexpectedCommand`] if any other message is received, and with
+    /// [`BitcoinMessageError::NonceConflict`] if the nonce matches the one we sent.
+    pub async fn recv_version(&mut self) -> Result<VersionData, BitcoinMessageError> {
+        let version = self.recv().await?.into_remote_version(self.local_nonce)?;
+        self.remote_version = Some(version.clone());
+
+        Ok(version)
+    }
+
+    /// Receives the `version` of the other side like [`Peer::recv_version`], failing with
+    /// [`BitcoinMessageError::VersionTimeout`] if it doesn't arrive within `timeout`.
+    pub async fn recv_version_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<VersionData, BitcoinMessageError> {
+        tokio::time::timeout(timeout, self.recv_version())
+            .await
+            .map_err(|_| BitcoinMessageError::VersionTimeout)?
+    }
+
+    /// Sends `sendaddrv2`, announcing support for `addrv2` messages. See [BIP 155](https://github.com/bitcoin/bips/blob/master/bip-0155.mediawiki).
+    ///
+    /// Must be sent before [`Peer::send_verack`]. Whether the other side sent it too is recorded by
+    /// [`Peer::recv_verack`], see [`Peer::addrv2`].
+    pub async fn send_sendaddrv2(&mut self) -> Result<(), BitcoinMessageError> {
+        self.local_addrv2 = true;
+
+        self.send(Command::SendAddrV2, Payload::Empty).await
+    }
+
+    /// Sends the `verack`, acknowledging the received `version`. See [`Peer::send_version`] for the handshake steps.
+    pub async fn send_verack(&mut self) -> Result<(), BitcoinMessageError> {
+        self.send(Command::VerAck, Payload::Empty).await
+    }
+
+    /// Receives the `verack` of the other side, completing the handshake. See [`Peer::send_version`] for the
+    /// handshake steps.
+    ///
+    /// Feature negotiation messages received meanwhile (see [`Command::is_negotiation`]) are skipped, except for
+    /// `sendaddrv2` being recorded for [`Peer::addrv2`]. Fails with [`BitcoinMessageError::Rejected`] if the other
+    /// side rejected our `version`, and with [`BitcoinMessageError::UnexpectedCommand`] if any other message is
+    /// received.
+    pub async fn recv_verack(&mut self) -> Result<(), BitcoinMessageError> {
+        loop {
+            let message = self.recv().await?;
+            if message.completes_handshake()? {
+                return Ok(());
+            }
+            match message.command() {
+                Command::SendAddrV2 => self.remote_addrv2 = true,
+                c => tracing::debug!("skipping `{}` before `verack`", c),
+            }
+        }
+    }
+
+    /// Receives the `verack` of the other side like [`Peer::recv_verack`], failing with
+    /// [`BitcoinMessageError::VerAckTimeout`] if it doesn't arrive within `timeout`.
+    pub async fn recv_verack_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<(), BitcoinMessageError> {
+        tokio::time::timeout(timeout, self.recv_verack())
+            .await
+            .map_err(|_| BitcoinMessageError::VerAckTimeout)?
+    }
+
+    /// Receives the next message.
+    ///
+    /// Messages with unknown commands are consumed from the stream, and reported as
+    /// [`BitcoinMessageError::CommandNameUnknown`], so it is safe to call `recv` again afterwards. So are the
+    /// messages not in [`ReaderConfig::allowed_commands`], reported as [`BitcoinMessageError::UnexpectedCommand`].
+    /// After any other decoding error, e.g. a garbled header or a payload over the limit, the stream is no longer
+    /// aligned at a message, and the connection must be dropped.
+    ///
+    /// This method is cancel safe: partially received messages are kept in an internal buffer.
+    pub async fn recv(&mut self) -> Result<Message, BitcoinMessageError> {
+        loop {
+            let keepalive = match self.keepalive.as_mut() {
+                None => return self.stream.recv().await,
+                Some(k) => k,
+            };
+            let message = tokio::select! {
+                m = self.stream.recv() => m?,
+                _ = keepalive.interval.tick() => {
+                    let nonce = rand::random();
+                    keepalive.pending_nonce = Some(nonce);
+                    tracing::debug!("sending keepalive ping");
+                    self.send(Command::Ping, Payload::Ping(nonce)).await?;
+                    continue;
+                }
+            };
+            match message.payload() {
+                Payload::Ping(nonce) => self.send(Command::Pong, Payload::Pong(*nonce)).await?,
+                Payload::Pong(nonce) => {
+                    if let Some(k) = self.keepalive.as_mut() {
+                        if k.pending_nonce == Some(*nonce) {
+                            k.pending_nonce = None;
+                        } else {
+                            tracing::warn!("received pong with unexpected nonce `{}`", nonce);
+                        }
+                    }
+                }
+                _ => return Ok(message),
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> MessageStream<T> {
+    /// Creates new [`MessageStream`] over `stream`, with the default [`ReaderConfig`].
+    pub fn new(stream: T) -> Self {
+        Self::with_config(stream, ReaderConfig::default())
+    }
+
+    /// Creates new [`MessageStream`] over `stream`, with the given [`ReaderConfig`].
+    pub fn with_config(stream: T, config: ReaderConfig) -> Self {
+        Self {
+            stream,
+            config,
+            buffer: PooledBuffer::default(),
+            metrics: PeerMetrics::default(),
+            skip: 0,
+        }
+    }
+
+    /// Creates new [`MessageStream`] over `stream`, with the given [`ReaderConfig`], and its read buffer taken from
+    /// `pool`. The buffer is returned to the pool when the stream is dropped.
+    pub fn with_pool(stream: T, config: ReaderConfig, pool: &BufferPool) -> Self {
+        Self {
+            buffer: pool.get(),
+            ..Self::with_config(stream, config)
+        }
+    }
+
+    /// Returns the [`ReaderConfig`] used when receiving messages.
+    pub fn config(&self) -> &ReaderConfig {
+        &self.config
+    }
+
+    /// Returns a snapshot of the traffic counters of this stream.
+    pub fn metrics(&self) -> PeerMetrics {
+        self.metrics
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+
+    /// Consumes the message stream, returning the underlying stream. Buffered, partially received data is lost.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    /// Receives the next message, or `None` if the stream ended cleanly, between messages.
+    ///
+    /// Fails with [`BitcoinMessageError::CommandNameUnknown`] for messages with unknown commands, and with
+    /// [`BitcoinMessageError::UnexpectedCommand`] for the ones not in [`ReaderConfig::allowed_commands`]. Both are
+    /// consumed from the stream, so it is safe to call `next_message` again afterwards. After any other decoding
+    /// error, e.g. a garbled header or a payload over the limit, the stream is no longer aligned at a message, and
+    /// the connection must be dropped. This method is cancel safe.
+    pub async fn next_message(&mut self) -> Result<Option<Message>, BitcoinMessageError> {
+        match self.recv().await {
+            Ok(m) => Ok(Some(m)),
+            Err(BitcoinMessageError::ConnectionClosed) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sends `message`.
+    pub async fn send_message(&mut self, message: &Message) -> Result<(), BitcoinMessageError> {
+        let bytes = message.to_bytes()?;
+        tracing::trace!("TX {:#?}", message);
+        self.stream.write_all(&bytes).await?;
+        self.metrics.bytes_sent += bytes.len() as u64;
+        self.metrics.messages_sent += 1;
+
+        Ok(())
+    }
+
+    /// Sends all `messages` with a single write. See [`write_messages`].
+    pub async fn send_messages(&mut self, messages: &[Message]) -> Result<(), BitcoinMessageError> {
+        let bytes_sent = write_messages(&mut self.stream, messages).await?;
+        self.metrics.bytes_sent += bytes_sent as u64;
+        self.metrics.messages_sent += messages.len() as u64;
+
+        Ok(())
+    }
+
+    /// Flushes pending writes and shuts down the write half of the stream. See [`Peer::disconnect`].
+    pub async fn shutdown(&mut self) -> Result<(), BitcoinMessageError> {
+        self.stream.flush().await?;
+        self.stream.shutdown().await?;
+
+        Ok(())
+    }
+
+    /// Like [`MessageStream::next_message`], but reports the end of stream as
+    /// [`BitcoinMessageError::ConnectionClosed`].
+    async fn recv(&mut self) -> Result<Message, BitcoinMessageError> {
+        read_frame(
+            &mut self.stream,
+            &mut self.buffer,
+            &mut self.skip,
+            &self.config,
+            &mut self.metrics,
+        )
+        .await
+    }
+}
+
+/// Serializes all `messages` into one buffer, and writes it with a single `write_all`.
+///
+/// Avoids extra syscalls and fragmented packets when sending several messages at once. Returns the number of bytes
+/// written.
+pub async fn write_messages<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    messages: &[Message],
+) -> Result<usize, BitcoinMessageError> {
+    let mut buf = Vec::new();
+    for message in messages {
+        buf.extend(message.to_bytes()?);
+        tracing::trace!("TX {:#?}", message);
+    }
+    writer.write_all(&buf).await?;
+
+    Ok(buf.len())
+}
+
+/// Sends a lone `verack` to `stream`, without a preceding `version`.
+///
+/// **This intentionally violates the protocol**, which requires `version` to be the first message: well-behaved
+/// peers ignore the `verack` or disconnect. Meant only for testing how peers react to misbehaving ones.
+pub async fn send_bare_verack<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    network: Network,
+) -> Result<(), BitcoinMessageError> {
+    let verack = Message::new(network.magic(), Command::VerAck, Payload::Empty);
+    write_messages(stream, &[verack]).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Reads the next frame from `stream`, buffering partial data in `buffer` and counting the traffic in `metrics`.
+///
+/// Payloads of frames rejected by `config` are discarded as they arrive, counting down `skip`, so they don't have to
+/// be buffered.
+async fn read_frame<T: AsyncRead + Unpin>(
+    stream: &mut T,
+    buffer: &mut Vec<u8>,
+    skip: &mut usize,
+    config: &ReaderConfig,
+    metrics: &mut PeerMetrics,
+) -> Result<Message, BitcoinMessageError> {
+    loop {
+        let skipped = (*skip).min(buffer.len());
+        buffer.drain(..skipped);
+        *skip -= skipped;
+        if *skip == 0 && buffer.len() >= HEADER_SIZE {
+            let header = match Header::from_bytes(&mut &buffer[..HEADER_SIZE]) {
+                Ok(h) => h,
+                Err(e) => {
+                    // the frame boundary can't be trusted anymore
+                    buffer.clear();
+                    return Err(e);
+                }
+            };
+            if let Err(e) = config.check(&header) {
+                match e {
+                    // the header is fine, so skip the payload and stay aligned
+                    BitcoinMessageError::UnexpectedCommand(_)
+                    | BitcoinMessageError::CommandNameUnknown(_) => {
+                        buffer.drain(..HEADER_SIZE);
+                        *skip = header.payload_len;
+                        metrics.messages_received += 1;
+                    }
+                    _ => buffer.clear(),
+                }
+                return Err(e);
+            }
+            let frame_len = HEADER_SIZE + header.payload_len;
+            if buffer.len() >= frame_len {
+                let result =
+                    header.read_payload(&mut &buffer[HEADER_SIZE..frame_len], config.strict);
+                buffer.drain(..frame_len);
+                metrics.messages_received += 1;
+                if let Ok(ref m) = result {
+                    tracing::trace!("RX {:#?}", m);
+                }
+
+                return result;
+            }
+            buffer.reserve(frame_len - buffer.len());
+        }
+        match stream.read_buf(buffer).await? {
+            0 if buffer.is_empty() => return Err(BitcoinMessageError::ConnectionClosed),
+            0 => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+            n => metrics.bytes_received += n as u64,
+        }
+    }
+}
+
+/// Downloads the block identified by `block_hash` from `peer`, which must have completed the handshake.
+///
+/// Sends a `getdata` request and waits for the `block` response, skipping any other messages received meanwhile,
+/// including blocks with a different hash.
+/// Returns [`BitcoinMessageError::BlockNotFound`] if the peer doesn't have the block, and
+/// [`BitcoinMessageError::BlockTimeout`] if it doesn't arrive within `timeout`.
+pub async fn download_block<T: AsyncRead + AsyncWrite + Unpin>(
+    peer: &mut Peer<T>,
+    block_hash: Hash,
+    timeout: Duration,
+) -> Result<Block, BitcoinMessageError> {
+    let request = vec![InventoryVector::new(InventoryType::Block, block_hash)];
+    peer.send(Command::GetData, Payload::GetData(request))
+        .await?;
+
+    let wait_for_block = async {
+        loop {
+            match peer.recv().await {
+                Ok(m) => match m.payload() {
+                    Payload::Block(block) if block.header().block_hash() == block_hash => {
+                        return Ok(block.clone())
+                    }
+                    Payload::NotFound(items) if items.iter().any(|i| *i.hash() == block_hash) => {
+                        return Err(BitcoinMessageError::BlockNotFound(block_hash))
+                    }
+                    _ => tracing::debug!("skipping `{}` while waiting for block", m.command()),
+                },
+                Err(BitcoinMessageError::CommandNameUnknown(c)) => {
+                    tracing::debug!("skipping `{}` while waiting for block", c)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, wait_for_block)
+        .await
+        .map_err(|_| BitcoinMessageError::BlockTimeout(block_hash))?
+}
+
+/// Downloads the headers following `locator` from `peer`, which must have completed the handshake.
+///
+/// Keeps sending `getheaders`, continuing from the last received header, until the peer returns fewer than
+/// [`MAX_HEADERS_LEN`] headers, i.e. its tip was reached. Returns [`BitcoinMessageError::HeadersTimeout`] if a
+/// `headers` response doesn't arrive within `timeout`. See [`crate::headers::block_locator`] for building `locator`.
+pub async fn sync_headers<T: AsyncRead + AsyncWrite + Unpin>(
+    peer: &mut Peer<T>,
+    locator: Vec<Hash>,
+    timeout: Duration,
+) -> Result<Vec<BlockHeader>, BitcoinMessageError> {
+    let mut locator = locator;
+    let mut headers = Vec::new();
+    loop {
+        let request = GetHeaders::new(PROTOCOL_VERSION as u32, locator, Hash::default());
+        peer.send(Command::GetHeaders, Payload::GetHeaders(request))
+            .await?;
+
+        let wait_for_headers = async {
+            loop {
+                match peer.recv().await {
+                    Ok(m) => match m.payload() {
+                        Payload::Headers(h) => return Ok(h.clone()),
+                        _ => {
+                            tracing::debug!("skipping `{}` while waiting for headers", m.command())
+                        }
+                    },
+                    Err(BitcoinMessageError::CommandNameUnknown(c)) => {
+                        tracing::debug!("skipping `{}` while waiting for headers", c)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+        let batch = tokio::time::timeout(timeout, wait_for_headers)
+            .await
+            .map_err(|_| BitcoinMessageError::HeadersTimeout)??;
+        tracing::debug!("received {} headers", batch.len());
+
+        let last = match batch.last() {
+            Some(last) => *last,
+            None => return Ok(headers),
+        };
+        let done = batch.len() < MAX_HEADERS_LEN;
+        headers.extend(batch);
+        if done {
+            return Ok(headers);
+        }
+        locator = vec![last.block_hash()];
+    }
+}
+
+/// Reads a `headers` message from `stream` without buffering its payload, passing each header to `on_header` as
+/// soon as it's decoded. Returns the number of headers.
+///
+/// Only one decoded header is held at a time, while a [`MessageStream`] buffers the whole payload of up to
+/// [`MAX_HEADERS_LEN`] headers and decodes all of them. The checksum can only be verified after the last header, so on
+/// [`BitcoinMessageError::ChecksumMismatch`] the headers already passed to `on_header` must be discarded.
+///
+/// Fails with [`BitcoinMessageError::UnexpectedCommand`] if the next message isn't `headers`. Its payload is
+/// skipped, so the stream stays aligned at the next message. Must not be mixed with reads through a
+/// [`MessageStream`] or [`Peer`] on the same stream, as they may have buffered part of the message already.
+pub async fn read_headers_streaming<T: AsyncRead + Unpin>(
+    stream: &mut T,
+    mut on_header: impl FnMut(BlockHeader),
+) -> Result<usize, BitcoinMessageError> {
+    let mut header_bytes = [0u8; HEADER_SIZE];
+    stream.read_exact(&mut header_bytes).await?;
+    let header = Header::from_bytes(&mut header_bytes.as_slice())?;
+    let mut payload = PayloadReader {
+        stream,
+        hasher: Sha256::new(),
+        remaining: header.payload_len,
+    };
+    let command = match header.command() {
+        Ok(c) => c,
+        Err(e) => {
+            payload.skip_rest().await?;
+            return Err(e);
+        }
+    };
+    if command != Command::Headers {
+        payload.skip_rest().await?;
+        return Err(BitcoinMessageError::UnexpectedCommand(command));
+    }
+
+    let count = payload.read_var_int().await?.0 as usize;
+    if count > MAX_HEADERS_LEN {
+        payload.skip_rest().await?;
+        return Err(BitcoinMessageError::HeadersTooLarge { count });
+    }
+    let mut buf = [0u8; BLOCK_HEADER_SIZE];
+    for _ in 0..count {
+        payload.read_exact(&mut buf).await?;
+        let block_header = BlockHeader::from_bytes(&mut buf.as_slice())?;
+        // transaction count, always 0
+        payload.read_var_int().await?;
+        on_header(block_header);
+    }
+    payload.skip_rest().await?;
+    if Sha256::digest(payload.hasher.finalize())[..CHECKSUM_SIZE] != header.checksum {
+        return Err(BitcoinMessageError::ChecksumMismatch);
+    }
+
+    Ok(count)
+}
+
+/// Reads a payload of known length from a stream, hashing it for the checksum on the way.
+struct PayloadReader<'a, T> {
+    stream: &'a mut T,
+    hasher: Sha256,
+    remaining: usize,
+}
+
+impl<T: AsyncRead + Unpin> PayloadReader<'_, T> {
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), BitcoinMessageError> {
+        if buf.len() > self.remaining {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        self.stream.read_exact(buf).await?;
+        self.hasher.update(&*buf);
+        self.remaining -= buf.len();
+
+        Ok(())
+    }
+
+    async fn read_var_int(&mut self) -> Result<VarInt, BitcoinMessageError> {
+        let mut buf = [0u8; 9];
+        self.read_exact(&mut buf[..1]).await?;
+        let len = match buf[0] {
+            0xfd => 3,
+            0xfe => 5,
+            0xff => 9,
+            _ => 1,
+        };
+        self.read_exact(&mut buf[1..len]).await?;
+
+        VarInt::from_bytes(&mut &buf[..len])
+    }
+
+    /// Reads (and hashes) the rest of the payload.
+    async fn skip_rest(&mut self) -> Result<(), BitcoinMessageError> {
+        let mut buf = [0u8; 1024];
+        while self.remaining > 0 {
+            let n = self.remaining.min(buf.len());
+            self.read_exact(&mut buf[..n]).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::tests::GENESIS_BLOCK,
+        enums::RejectCode,
+        message::{tests::version_data, SendCmpct},
+        reject::RejectData,
+        utils::checksum,
+    };
+    use hex_literal::hex;
+    use tokio::io::{duplex, DuplexStream};
+
+    const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+    fn raw_frame(command_name: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut frame = Network::Mainnet.magic().to_vec();
+        frame.extend(command_name);
+        frame.resize(16, 0);
+        frame.extend((payload.len() as u32).to_le_bytes());
+        frame.extend(checksum(payload));
+        frame.extend(payload);
+
+        frame
+    }
+
+    fn peers() -> (Peer<DuplexStream>, Peer<DuplexStream>) {
+        let (local, remote) = duplex(64 * 1024);
+
+        (
+            Peer::new(local, Network::Mainnet),
+            Peer::new(remote, Network::Mainnet),
+        )
+    }
+
+    #[tokio::test]
+    async fn download_block_skips_other_messages() {
+        let (mut local, mut remote) = peers();
+        let hash: Hash = GENESIS_HASH.parse().unwrap();
+        let remote_task = tokio::spawn(async move {
+            let request = remote.recv().await.unwrap();
+            let block = Block::from_bytes(&mut GENESIS_BLOCK.as_slice()).unwrap();
+            remote
+                .send(Command::Inv, Payload::Inv(vec![]))
+                .await
+                .unwrap();
+            let ping = raw_frame(b"ping", &[0u8; 8]);
+            remote.stream.stream.write_all(&ping).await.unwrap();
+            let other = Block::new(
+                BlockHeader::new(1, Hash::default(), Hash::default(), 0, 0, 0),
+                vec![],
+            );
+            remote
+                .send(Command::Block, Payload::Block(other))
+                .await
+                .unwrap();
+            remote
+                .send(Command::Block, Payload::Block(block))
+                .await
+                .unwrap();
+
+            request
+        });
+
+        let block = download_block(&mut local, hash, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let request = remote_task.await.unwrap();
+
+        assert_eq!(block.to_bytes().unwrap(), GENESIS_BLOCK);
+        match request.payload() {
+            Payload::GetData(items) => {
+                assert_eq!(
+                    items,
+                    &vec![InventoryVector::new(InventoryType::Block, hash)]
+                )
+            }
+            p => panic!("expected getdata, got {:?}", p),
+        }
+    }
+
+    #[tokio::test]
+    async fn download_block_not_found() {
+        let (mut local, mut remote) = peers();
+        let hash: Hash = GENESIS_HASH.parse().unwrap();
+        tokio::spawn(async move {
+            if let Payload::GetData(items) = remote.recv().await.unwrap().payload() {
+                remote
+                    .send(Command::NotFound, Payload::NotFound(items.clone()))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let result = download_block(&mut local, hash, Duration::from_secs(5)).await;
+
+        assert!(matches!(result, Err(BitcoinMessageError::BlockNotFound(h)) if h == hash));
+    }
+
+    #[tokio::test]
+    async fn write_messages_concatenates_in_order() {
+        let messages = [
+            Message::new(Network::Mainnet.magic(), Command::VerAck, Payload::Empty),
+            Message::new(Network::Mainnet.magic(), Command::Ping, Payload::Ping(1)),
+            Message::new(Network::Mainnet.magic(), Command::Pong, Payload::Pong(2)),
+        ];
+        let mut out = Vec::new();
+
+        write_messages(&mut out, &messages).await.unwrap();
+
+        let expected: Vec<u8> = messages
+            .iter()
+            .flat_map(|m| m.to_bytes().unwrap())
+            .collect();
+        assert_eq!(out, expected);
+    }
+
+    #[tokio::test]
+    async fn send_bare_verack_sends_only_verack() {
+        let mut out = Vec::new();
+
+        send_bare_verack(&mut out, Network::Regtest).await.unwrap();
+
+        let message = Message::from_bytes(&mut out.as_slice()).unwrap();
+        assert_eq!(*message.command(), Command::VerAck);
+        assert_eq!(message.network(), Some(Network::Regtest));
+        assert_eq!(out.len(), HEADER_SIZE);
+    }
+
+    #[tokio::test]
+    async fn handshake_outbound_and_inbound() {
+        let (mut outbound, mut inbound) = peers();
+        let inbound_task = tokio::spawn(async move {
+            let version = inbound.recv_version().await.unwrap();
+            inbound.send_version(version_data(2)).await.unwrap();
+            inbound.send_verack().await.unwrap();
+            inbound.recv_verack().await.unwrap();
+
+            *version.nonce()
+        });
+
+        outbound.send_version(version_data(1)).await.unwrap();
+        let version = outbound.recv_version().await.unwrap();
+        outbound.send_verack().await.unwrap();
+        outbound.recv_verack().await.unwrap();
+
+        assert_eq!(*version.nonce(), 2);
+        assert_eq!(inbound_task.await.unwrap(), 1);
+        assert_eq!(outbound.negotiated_version(), Some(crate::PROTOCOL_VERSION));
+        assert_eq!(outbound.peer_version(), Some(&version_data(2)));
+    }
+
+    #[tokio::test]
+    async fn recv_version_times_out() {
+        let (mut local, _remote) = peers();
+
+        let result = local.recv_version_timeout(Duration::from_millis(50)).await;
+
+        assert!(matches!(result, Err(BitcoinMessageError::VersionTimeout)));
+    }
+
+    #[tokio::test]
+    async fn recv_verack_times_out_after_version() {
+        let (mut local, mut remote) = peers();
+        remote.send_version(version_data(2)).await.unwrap();
+
+        let version = local.recv_version_timeout(Duration::from_millis(50)).await;
+        let result = local.recv_verack_timeout(Duration::from_millis(50)).await;
+
+        assert_eq!(*version.unwrap().nonce(), 2);
+        assert!(matches!(result, Err(BitcoinMessageError::VerAckTimeout)));
+    }
+
+    #[tokio::test]
+    async fn recv_verack_skips_negotiation_messages() {
+        let (mut local, mut remote) = peers();
+        remote
+            .send(Command::WtxidRelay, Payload::Empty)
+            .await
+            .unwrap();
+        remote
+            .send(
+                Command::SendCmpct,
+                Payload::SendCmpct(SendCmpct::new(false, 2)),
+            )
+            .await
+            .unwrap();
+        remote.send_verack().await.unwrap();
+        remote
+            .send(Command::Inv, Payload::Inv(vec![]))
+            .await
+            .unwrap();
+
+        local.recv_verack().await.unwrap();
+        assert!(matches!(
+            local.recv_verack().await,
+            Err(BitcoinMessageError::UnexpectedCommand(Command::Inv))
+        ));
+    }
+
+    #[tokio::test]
+    async fn handshake_negotiates_addrv2() {
+        let (mut local, mut remote) = peers();
+        local.send_sendaddrv2().await.unwrap();
+        remote.send_sendaddrv2().await.unwrap();
+        local.send_verack().await.unwrap();
+        remote.send_verack().await.unwrap();
+
+        assert!(!local.addrv2());
+        local.recv_verack().await.unwrap();
+        remote.recv_verack().await.unwrap();
+
+        assert!(local.addrv2());
+        assert!(remote.addrv2());
+    }
+
+    #[tokio::test]
+    async fn addrv2_requires_both_sides() {
+        let (mut local, mut remote) = peers();
+        remote.send_sendaddrv2().await.unwrap();
+        remote.send_verack().await.unwrap();
+
+        local.recv_verack().await.unwrap();
+
+        assert!(!local.addrv2());
+    }
+
+    #[tokio::test]
+    async fn metrics_count_messages_and_bytes() {
+        let (mut local, mut remote) = peers();
+        let messages = [
+            Message::new(Network::Mainnet.magic(), Command::Ping, Payload::Ping(1)),
+            Message::new(Network::Mainnet.magic(), Command::Pong, Payload::Pong(2)),
+        ];
+        local.send_verack().await.unwrap();
+        local.send_messages(&messages).await.unwrap();
+
+        for _ in 0..3 {
+            remote.recv().await.unwrap();
+        }
+
+        let expected_bytes = (3 * HEADER_SIZE + 2 * 8) as u64;
+        assert_eq!(*local.metrics().messages_sent(), 3);
+        assert_eq!(*local.metrics().bytes_sent(), expected_bytes);
+        assert_eq!(*remote.metrics().messages_received(), 3);
+        assert_eq!(*remote.metrics().bytes_received(), expected_bytes);
+        assert_eq!(*local.metrics().messages_received(), 0);
+        assert_eq!(*remote.metrics().bytes_sent(), 0);
+    }
+
+    #[tokio::test]
+    async fn pooled_buffer_is_returned_on_drop() {
+        let pool = BufferPool::new(1);
+        let (local, remote) = duplex(64 * 1024);
+        let mut local = Peer::with_pool(local, Network::Mainnet, ReaderConfig::default(), &pool);
+        let mut remote = Peer::new(remote, Network::Mainnet);
+        remote.send_verack().await.unwrap();
+
+        local.recv_verack().await.unwrap();
+        assert!(pool.is_empty());
+        drop(local);
+
+        assert_eq!(pool.len(), 1);
+        assert!(pool.get().capacity() >= HEADER_SIZE);
+    }
+
+    #[tokio::test]
+    async fn handshake_detects_nonce_conflict() {
+        let (mut local, mut remote) = peers();
+        local.send_version(version_data(7)).await.unwrap();
+        remote.send_version(version_data(7)).await.unwrap();
+
+        assert!(matches!(
+            local.recv_version().await,
+            Err(BitcoinMessageError::NonceConflict)
+        ));
+    }
+
+    #[tokio::test]
+    async fn recv_version_rejects_other_messages() {
+        let (mut local, mut remote) = peers();
+        remote.send_verack().await.unwrap();
+
+        assert!(matches!(
+            local.recv_version().await,
+            Err(BitcoinMessageError::UnexpectedCommand(Command::VerAck))
+        ));
+    }
+
+    #[tokio::test]
+    async fn handshake_reports_reject() {
+        let (mut local, mut remote) = peers();
+        let reject = RejectData::new("version", RejectCode::Obsolete, "old version", None);
+        local.send_version(version_data(1)).await.unwrap();
+        remote.recv_version().await.unwrap();
+        remote
+            .send(Command::Reject, Payload::Reject(reject.clone()))
+            .await
+            .unwrap();
+
+        local.send_verack().await.unwrap();
+        let result = local.recv_verack().await;
+
+        assert!(matches!(result, Err(BitcoinMessageError::Rejected(r)) if r == reject));
+    }
+
+    #[tokio::test]
+    async fn disconnect_shuts_down_after_pending_writes() {
+        let (mut local, mut remote) = peers();
+        local.send_verack().await.unwrap();
+
+        local.disconnect().await.unwrap();
+
+        assert_eq!(*remote.recv().await.unwrap().command(), Command::VerAck);
+        assert!(matches!(
+            remote.recv().await,
+            Err(BitcoinMessageError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn message_stream_exchange() {
+        let (a, b) = tokio::io::duplex(1024);
+        let (mut local, mut remote) = (MessageStream::new(a), MessageStream::new(b));
+        let magic = Network::Mainnet.magic();
+        let remote_task = tokio::spawn(async move {
+            let ping = remote.next_message().await.unwrap().unwrap();
+            let nonce = match ping.payload() {
+                Payload::Ping(n) => *n,
+                _ => panic!("expected ping"),
+            };
+            let pong = Message::new(magic, Command::Pong, Payload::Pong(nonce));
+            remote.send_message(&pong).await.unwrap();
+            remote.shutdown().await.unwrap();
+
+            remote.next_message().await.unwrap()
+        });
+
+        local
+            .send_message(&Message::new(magic, Command::Ping, Payload::Ping(42)))
+            .await
+            .unwrap();
+        let pong = local.next_message().await.unwrap().unwrap();
+        let end = local.next_message().await.unwrap();
+        drop(local);
+
+        assert!(matches!(pong.payload(), Payload::Pong(42)));
+        assert!(end.is_none());
+        assert!(remote_task.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn recv_reports_closed_connection() {
+        let (mut local, remote) = peers();
+        drop(remote);
+
+        assert!(matches!(
+            local.recv().await,
+            Err(BitcoinMessageError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn recv_handles_partial_writes() {
+        let (mut local, mut remote) = peers();
+        let frame = Message::new(Network::Mainnet.magic(), Command::Ping, Payload::Ping(7))
+            .to_bytes()
+            .unwrap();
+        tokio::spawn(async move {
+            for b in frame {
+                remote.stream.stream.write_all(&[b]).await.unwrap();
+            }
+        });
+
+        let message = local.recv().await.unwrap();
+
+        assert!(matches!(message.payload(), Payload::Ping(7)));
+    }
+
+    #[tokio::test]
+    async fn message_stream_skips_rejected_payloads() {
+        let (a, mut b) = duplex(1024);
+        let mut local = MessageStream::with_config(a, ReaderConfig::handshake());
+        let payload = [0u8; 3000];
+        let mut inv = hex!("f9beb4d9696e76000000000000000000b80b0000").to_vec();
+        inv.extend(checksum(&payload));
+        inv.extend(payload);
+        tokio::spawn(async move {
+            b.write_all(&inv).await.unwrap();
+            send_bare_verack(&mut b, Network::Mainnet).await.unwrap();
+        });
+
+        assert!(matches!(
+            local.next_message().await,
+            Err(BitcoinMessageError::UnexpectedCommand(Command::Inv))
+        ));
+        let verack = local.next_message().await.unwrap().unwrap();
+
+        assert_eq!(*verack.command(), Command::VerAck);
+    }
+
+    #[tokio::test]
+    async fn keepalive_sends_ping() {
+        let (mut local, mut remote) = peers();
+        local.enable_keepalive(Duration::from_millis(10));
+        // drive the local keepalive until the remote side sees a ping
+        let local_task = tokio::spawn(async move { local.recv().await });
+
+        let message = remote.recv().await.unwrap();
+
+        assert_eq!(*message.command(), Command::Ping);
+        local_task.abort();
+    }
+
+    #[tokio::test]
+    async fn keepalive_answers_ping_and_ignores_unexpected_pong() {
+        let (mut local, mut remote) = peers();
+        local.enable_keepalive(Duration::from_secs(60));
+        remote.send(Command::Ping, Payload::Ping(42)).await.unwrap();
+        remote.send(Command::Pong, Payload::Pong(1)).await.unwrap();
+        remote.send(Command::VerAck, Payload::Empty).await.unwrap();
+
+        let message = local.recv().await.unwrap();
+        let answer = remote.recv().await.unwrap();
+
+        assert_eq!(*message.command(), Command::VerAck);
+        assert!(matches!(answer.payload(), Payload::Pong(42)));
+    }
+
+    #[tokio::test]
+    async fn sync_headers_continues_until_tip() {
+        let (mut local, mut remote) = peers();
+        let batch: Vec<_> = (0..MAX_HEADERS_LEN as u32)
+            .map(|i| BlockHeader::new(1, Hash::default(), Hash::default(), i, 0, 0))
+            .collect();
+        let last = *batch.last().unwrap();
+        let remote_task = tokio::spawn(async move {
+            remote.recv().await.unwrap();
+            remote
+                .send(Command::Headers, Payload::Headers(batch))
+                .await
+                .unwrap();
+            let request = remote.recv().await.unwrap();
+            remote
+                .send(Command::Headers, Payload::Headers(vec![last]))
+                .await
+                .unwrap();
+
+            request
+        });
+
+        let headers = sync_headers(&mut local, vec![Hash::default()], Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(headers.len(), MAX_HEADERS_LEN + 1);
+        let request = remote_task.await.unwrap();
+        let Payload::GetHeaders(request) = request.payload() else {
+            panic!("expected getheaders");
+        };
+        assert_eq!(*request.locator(), vec![last.block_hash()]);
+    }
+
+    #[tokio::test]
+    async fn sync_headers_stops_on_empty_headers() {
+        let (mut local, mut remote) = peers();
+        tokio::spawn(async move {
+            remote.recv().await.unwrap();
+            remote
+                .send(Command::Headers, Payload::Headers(vec![]))
+                .await
+                .unwrap();
+        });
+
+        let headers = sync_headers(&mut local, vec![Hash::default()], Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(headers.is_empty());
+    }
+
+    fn headers_frame(count: usize) -> Vec<u8> {
+        let block = Block::from_bytes(&mut GENESIS_BLOCK.as_slice()).unwrap();
+        let headers = vec![*block.header(); count];
+
+        Message::new(
+            Network::Mainnet.magic(),
+            Command::Headers,
+            Payload::Headers(headers),
+        )
+        .to_bytes()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn read_headers_streaming_matches_buffered() {
+        let frame = headers_frame(MAX_HEADERS_LEN);
+        let mut headers = Vec::new();
+
+        let count = read_headers_streaming(&mut frame.as_slice(), |h| headers.push(h))
+            .await
+            .unwrap();
+
+        let message = Message::from_bytes(&mut frame.as_slice()).unwrap();
+        assert_eq!(count, MAX_HEADERS_LEN);
+        assert_eq!(*message.payload(), Payload::Headers(headers));
+    }
+
+    #[tokio::test]
+    async fn read_headers_streaming_skips_other_messages() {
+        let ping = Message::new(Network::Mainnet.magic(), Command::Ping, Payload::Ping(1));
+        let mut data = ping.to_bytes().unwrap();
+        data.extend(headers_frame(2));
+        let mut stream = data.as_slice();
+
+        let first = read_headers_streaming(&mut stream, |_| {}).await;
+        let second = read_headers_streaming(&mut stream, |_| {}).await;
+
+        assert!(matches!(
+            first,
+            Err(BitcoinMessageError::UnexpectedCommand(Command::Ping))
+        ));
+        assert_eq!(second.unwrap(), 2);
+        assert!(stream.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_headers_streaming_checks_checksum() {
+        let mut frame = headers_frame(1);
+        frame[20] ^= 0xff;
+
+        assert!(matches!(
+            read_headers_streaming(&mut frame.as_slice(), |_| {}).await,
+            Err(BitcoinMessageError::ChecksumMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn download_block_times_out() {
+        let (mut local, _remote) = peers();
+        let hash: Hash = GENESIS_HASH.parse().unwrap();
+
+        let result = download_block(&mut local, hash, Duration::from_millis(50)).await;
+
+        assert!(matches!(result, Err(BitcoinMessageError::BlockTimeout(h)) if h == hash));
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// Largest capacity an idle buffer keeps, in bytes. Larger buffers, e.g. after receiving a block, are shrunk when
+/// returned to the [`BufferPool`].
+pub const MAX_IDLE_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// Pool of reusable read buffers, shared by many [`Peer`](crate::peer::Peer)s.
+///
+/// Cloning the pool is cheap, and all clones share the same buffers. At most `max_buffers` idle buffers are kept,
+/// any further returned buffers are freed. Idle buffers are shrunk to [`MAX_IDLE_BUFFER_CAPACITY`].
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    idle: Arc<Mutex<Vec<Vec<u8>>>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates new, empty [`BufferPool`], keeping at most `max_buffers` idle buffers.
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            idle: Arc::new(Mutex::new(Vec::new())),
+            max_buffers,
+        }
+    }
+
+    /// Takes an idle buffer from the pool, or allocates a new one if there is none.
+    ///
+    /// The buffer is returned to the pool when the [`PooledBuffer`] is dropped.
+    pub fn get(&self) -> PooledBuffer {
+        let buffer = self
+            .idle
+            .lock()
+            .ok()
+            .and_then(|mut idle| idle.pop())
+            .unwrap_or_default();
+
+        PooledBuffer {
+            buffer,
+            pool: Some(self.clone()),
+        }
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    pub fn len(&self) -> usize {
+        self.idle.lock().map(|idle| idle.len()).unwrap_or(0)
+    }
+
+    /// Returns `true` if the pool has no idle buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn put(&self, mut buffer: Vec<u8>) {
+        buffer.clear();
+        buffer.shrink_to(MAX_IDLE_BUFFER_CAPACITY);
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < self.max_buffers {
+                idle.push(buffer);
+            }
+        }
+    }
+}
+
+/// Buffer taken from a [`BufferPool`], returned to it on drop. Dereferences to the underlying `Vec<u8>`.
+///
+/// The [`Default`] buffer doesn't belong to any pool, and is simply freed on drop.
+#[derive(Debug, Default)]
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: Option<BufferPool>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.put(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::new(2);
+        let mut buffer = pool.get();
+        buffer.extend([0u8; 1024]);
+        let ptr = buffer.as_ptr();
+        drop(buffer);
+
+        assert_eq!(pool.len(), 1);
+        let buffer = pool.get();
+        assert!(pool.is_empty());
+        // the same allocation, emptied
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 1024);
+    }
+
+    #[test]
+    fn large_buffers_are_shrunk() {
+        let pool = BufferPool::new(1);
+        let mut buffer = pool.get();
+        buffer.extend(vec![0u8; 4 * MAX_IDLE_BUFFER_CAPACITY]);
+        drop(buffer);
+
+        assert!(pool.get().capacity() <= MAX_IDLE_BUFFER_CAPACITY);
+    }
+
+    #[test]
+    fn pool_keeps_at_most_max_buffers() {
+        let pool = BufferPool::new(2);
+        let buffers: Vec<_> = (0..3).map(|_| pool.get()).collect();
+
+        drop(buffers);
+
+        assert_eq!(pool.len(), 2);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{
+    enums::{Command, Network},
+    errors::BitcoinMessageError,
+    message::{BitcoinDeserialize, Header, Message, MAX_SIZE},
+};
+use std::{collections::HashSet, io::Read};
+
+/// Configuration of a [`MessageReader`].
+#[derive(Debug, Clone)]
+pub struct ReaderConfig {
+    /// Commands the reader is willing to accept. Any other command results in a
+    /// [`BitcoinMessageError::UnexpectedCommand`] error, and unknown commands in a
+    /// [`BitcoinMessageError::CommandNameUnknown`] error, before the payload is read. The payload is skipped, so
+    /// reading can go on. `None` accepts all commands.
+    pub allowed_commands: Option<HashSet<Command>>,
+
+    /// Largest payload the reader is willing to accept. Larger payloads result in a
+    /// [`BitcoinMessageError::PayloadTooBig`] error, before the payload is read. Defaults to [`MAX_SIZE`].
+    pub max_payload_size: usize,
+
+    /// Whether to [`resync`] and read on, after a message fails its checksum or has a garbled header.
+    /// Defaults to `false`, returning the error instead. Only used by [`MessageReader`].
+    pub resync: bool,
+
+    /// Network of the messages, whose `start_string` [`resync`] looks for. Required to resync on a
+    /// [`Network::Custom`]. Defaults to `None`, looking for any of the known networks.
+    pub network: Option<Network>,
+
+    /// Whether payload bytes left over by the payload parser, or boolean fields other than `0x00`/`0x01`, are an
+    /// error. See [`Message::from_bytes_strict`].
+    /// Defaults to `false`, ignoring them.
+    pub strict: bool,
+}
+
+impl ReaderConfig {
+    /// Creates a [`ReaderConfig`] accepting only the messages valid before a handshake completes: `version`,
+    /// `verack`, the feature negotiation messages (see [`Command::is_negotiation`]), and `reject`.
+    pub fn handshake() -> Self {
+        Self {
+            allowed_commands: Some(HashSet::from([
+                Command::Version,
+                Command::VerAck,
+                Command::WtxidRelay,
+                Command::SendAddrV2,
+                Command::SendHeaders,
+                Command::SendCmpct,
+                Command::Reject,
+            ])),
+            ..Default::default()
+        }
+    }
+}
+
+impl ReaderConfig {
+    /// Checks `header` against this configuration, before the payload is read.
+    pub(crate) fn check(&self, header: &Header) -> Result<(), BitcoinMessageError> {
+        // without an allowlist, unknown commands are reported by `Header::read_payload`, after reading the payload
+        if let Some(allowed) = &self.allowed_commands {
+            let command = header.command()?;
+            if !allowed.contains(&command) {
+                return Err(BitcoinMessageError::UnexpectedCommand(command));
+            }
+        }
+        if header.payload_len > self.max_payload_size {
+            return Err(BitcoinMessageError::PayloadTooBig {
+                size: header.payload_len,
+                max: self.max_payload_size,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ReaderConfig {
+    fn default() -> Self {
+        Self {
+            allowed_commands: None,
+            max_payload_size: MAX_SIZE,
+            resync: false,
+            network: None,
+            strict: false,
+        }
+    }
+}
+
+/// Discards bytes from `reader` until the `start_string` of `network` is found, or of any of the known [`Network`]s
+/// if `network` is `None`.
+///
+/// Returns the `start_string`, which has been consumed from `reader` as well.
+pub fn resync(
+    reader: &mut impl Read,
+    network: Option<Network>,
+) -> Result<[u8; 4], BitcoinMessageError> {
+    let found = |window: [u8; 4]| match network {
+        Some(network) => window == network.magic(),
+        None => Network::from_magic(window).is_some(),
+    };
+    let mut window = [0u8; 4];
+    reader.read_exact(&mut window)?;
+    let mut skipped = 0usize;
+    while !found(window) {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        window.rotate_left(1);
+        window[3] = byte[0];
+        skipped += 1;
+    }
+    tracing::debug!("resynced after skipping {} bytes", skipped);
+
+    Ok(window)
+}
+
+/// Reads consecutive [`Message`]s from the underlying reader.
+#[derive(Debug)]
+pub struct MessageReader<R> {
+    inner: R,
+    config: ReaderConfig,
+    // `start_string` already consumed by `resync`
+    pending_magic: Option<[u8; 4]>,
+}
+
+impl<R: Read> MessageReader<R> {
+    /// Creates new [`MessageReader`] with the default [`ReaderConfig`].
+    pub fn new(inner: R) -> Self {
+        Self::with_config(inner, ReaderConfig::default())
+    }
+
+    /// Creates new [`MessageReader`] with the given [`ReaderConfig`].
+    pub fn with_config(inner: R, config: ReaderConfig) -> Self {
+        Self {
+            inner,
+            config,
+            pending_magic: None,
+        }
+    }
+
+    /// Returns the [`ReaderConfig`] used by this reader.
+    pub fn config(&self) -> &ReaderConfig {
+        &self.config
+    }
+
+    /// Consumes the reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Skips to the start of the next message, after the stream lost alignment. See [`resync`].
+    pub fn resync(&mut self) -> Result<(), BitcoinMessageError> {
+        self.pending_magic = Some(resync(&mut self.inner, self.config.network)?);
+
+        Ok(())
+    }
+
+    /// Reads the next [`Message`].
+    ///
+    /// With [`ReaderConfig::resync`] set, corrupted messages are skipped instead of being reported.
+    pub fn read_message(&mut self) -> Result<Message, BitcoinMessageError> {
+        loop {
+            match self.read_frame() {
+                Err(
+                    ref e @ (BitcoinMessageError::ChecksumMismatch
+                    | BitcoinMessageError::CommandNameNonAscii
+                    | BitcoinMessageError::EmptyCommandName
+                    | BitcoinMessageError::MalformedCommandPadding(_)),
+                ) if self.config.resync => {
+                    tracing::warn!("skipping corrupted message: {}", e);
+                    self.resync()?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Message, BitcoinMessageError> {
+        let header = match self.pending_magic.take() {
+            Some(magic) => Header::from_bytes(&mut magic.as_slice().chain(&mut self.inner)),
+            None => Header::from_bytes(&mut self.inner),
+        }?;
+        if let Err(e) = self.config.check(&header) {
+            if matches!(
+                e,
+                BitcoinMessageError::UnexpectedCommand(_)
+                    | BitcoinMessageError::CommandNameUnknown(_)
+            ) {
+                // the header is fine, so skip the payload and stay aligned
+                std::io::copy(
+                    &mut (&mut self.inner).take(header.payload_len as u64),
+                    &mut std::io::sink(),
+                )?;
+            }
+            return Err(e);
+        }
+
+        header.read_payload(&mut self.inner, self.config.strict)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::message::{BitcoinSerialize, Payload};
+    use hex_literal::hex;
+
+    const VERACK: [u8; 24] = hex!("f9beb4d976657261636b000000000000000000005df6e0e2");
+
+    #[test]
+    fn reader_accepts_allowed_command() {
+        let mut reader = MessageReader::with_config(VERACK.as_slice(), ReaderConfig::handshake());
+
+        let message = reader.read_message().unwrap();
+
+        assert_eq!(*message.command(), Command::VerAck);
+    }
+
+    #[test]
+    fn reader_rejects_disallowed_command() {
+        let config = ReaderConfig {
+            allowed_commands: Some(HashSet::from([Command::Version])),
+            ..Default::default()
+        };
+        let mut reader = MessageReader::with_config(VERACK.as_slice(), config);
+
+        let result = reader.read_message();
+
+        assert!(matches!(
+            result,
+            Err(BitcoinMessageError::UnexpectedCommand(Command::VerAck))
+        ));
+    }
+
+    #[test]
+    fn reader_skips_rejected_payloads() {
+        let ping = hex!("f9beb4d970696e67000000000000000008000000553693da75294d6c0a1f3b8e");
+        let mut unknown = hex!("f9beb4d9666f6f00000000000000000004000000").to_vec();
+        unknown.extend(crate::utils::checksum(b"abcd"));
+        unknown.extend(b"abcd");
+        let data = [ping.as_slice(), &unknown, &VERACK].concat();
+        let mut reader = MessageReader::with_config(data.as_slice(), ReaderConfig::handshake());
+
+        assert!(matches!(
+            reader.read_message(),
+            Err(BitcoinMessageError::UnexpectedCommand(Command::Ping))
+        ));
+        assert!(matches!(
+            reader.read_message(),
+            Err(BitcoinMessageError::CommandNameUnknown(c)) if c == "foo"
+        ));
+        assert_eq!(*reader.read_message().unwrap().command(), Command::VerAck);
+    }
+
+    #[test]
+    fn reader_rejects_payload_over_configured_limit() {
+        // an empty `inv`, padded to within the plausible size of `inv`
+        let payload = vec![0u8; 1024 * 1024];
+        let mut data = hex!("f9beb4d9696e76000000000000000000").to_vec();
+        data.extend((payload.len() as u32).to_le_bytes());
+        data.extend(crate::utils::checksum(&payload));
+        data.extend(&payload);
+        let config = ReaderConfig {
+            max_payload_size: 64 * 1024,
+            ..Default::default()
+        };
+
+        let limited = MessageReader::with_config(data.as_slice(), config).read_message();
+        let unlimited = MessageReader::new(data.as_slice()).read_message();
+
+        assert!(matches!(
+            limited,
+            Err(BitcoinMessageError::PayloadTooBig { max, .. }) if max == 64 * 1024
+        ));
+        assert!(unlimited.is_ok());
+    }
+
+    #[test]
+    fn reader_reads_consecutive_messages() {
+        let data = [VERACK, VERACK].concat();
+        let mut reader = MessageReader::new(data.as_slice());
+
+        assert!(reader.read_message().is_ok());
+        assert!(reader.read_message().is_ok());
+        assert!(matches!(
+            reader.read_message(),
+            Err(BitcoinMessageError::ConnectionClosed)
+        ));
+    }
+
+    #[test]
+    fn resync_skips_to_next_start_string() {
+        let data = [b"garbage".as_slice(), &VERACK].concat();
+        let mut reader = data.as_slice();
+
+        assert_eq!(
+            resync(&mut reader, None).unwrap(),
+            crate::START_STRING_MAINNET
+        );
+        assert_eq!(reader, &VERACK[4..]);
+    }
+
+    #[test]
+    fn reader_recovers_on_custom_network() {
+        let network = Network::Custom {
+            magic: [0x01, 0x02, 0x03, 0x04],
+            port: 28333,
+        };
+        let verack = Message::new(network.magic(), Command::VerAck, Payload::Empty)
+            .to_bytes()
+            .unwrap();
+        let mut corrupted = verack.clone();
+        corrupted[23] ^= 0xff;
+        let data = [corrupted.as_slice(), b"\x00\x01", &verack].concat();
+        let config = ReaderConfig {
+            resync: true,
+            network: Some(network),
+            ..Default::default()
+        };
+
+        let message = MessageReader::with_config(data.as_slice(), config)
+            .read_message()
+            .unwrap();
+
+        assert_eq!(*message.command(), Command::VerAck);
+        assert_eq!(*message.start_string(), network.magic());
+    }
+
+    #[test]
+    fn reader_recovers_from_corrupted_message() {
+        let mut corrupted = VERACK;
+        corrupted[23] ^= 0xff;
+        let data = [corrupted.as_slice(), b"\x00\x01", &VERACK].concat();
+
+        let mut reader = MessageReader::new(data.as_slice());
+        assert!(matches!(
+            reader.read_message(),
+            Err(BitcoinMessageError::ChecksumMismatch)
+        ));
+        reader.resync().unwrap();
+        let recovered = reader.read_message();
+        let config = ReaderConfig {
+            resync: true,
+            ..Default::default()
+        };
+        let automatic = MessageReader::with_config(data.as_slice(), config).read_message();
+
+        assert_eq!(*recovered.unwrap().command(), Command::VerAck);
+        assert_eq!(*automatic.unwrap().command(), Command::VerAck);
+    }
+}
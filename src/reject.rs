@@ -0,0 +1,203 @@
+use crate::{
+    enums::RejectCode,
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, WriteBytesExt},
+    message::{BitcoinDeserialize, BitcoinSerialize},
+    types::{read_var_bytes, var_bytes_len, write_array, write_var_bytes, Hash, HASH_SIZE},
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
+#[cfg(feature = "getset")]
+use getset::Getters;
+
+/// `reject` message payload: tells why a message was rejected. See [bitcoin docs](https://developer.bitcoin.org/reference/p2p_networking.html#reject).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "getset", derive(Getters))]
+pub struct RejectData {
+    /// Command name of the rejected message. Not parsed as a [`Command`](crate::enums::Command), as it may be one
+    /// unknown to this crate.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    message: String,
+
+    /// Why the message was rejected.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    ccode: RejectCode,
+
+    /// Human readable reason, as sent by the peer.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    reason: String,
+
+    /// TXID or block header hash of the rejected `tx` or `block`. Not sent for any other message.
+    #[cfg_attr(feature = "getset", getset(get = "pub"))]
+    hash: Option<Hash>,
+}
+
+#[cfg(not(feature = "getset"))]
+impl RejectData {
+    /// Command name of the rejected message. Not parsed as a [`Command`](crate::enums::Command), as it may be one
+    /// unknown to this crate.
+    #[inline(always)]
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+
+    /// Why the message was rejected.
+    #[inline(always)]
+    pub fn ccode(&self) -> &RejectCode {
+        &self.ccode
+    }
+
+    /// Human readable reason, as sent by the peer.
+    #[inline(always)]
+    pub fn reason(&self) -> &String {
+        &self.reason
+    }
+
+    /// TXID or block header hash of the rejected `tx` or `block`. Not sent for any other message.
+    #[inline(always)]
+    pub fn hash(&self) -> &Option<Hash> {
+        &self.hash
+    }
+}
+
+impl RejectData {
+    /// Creates new [`RejectData`]. `hash` is only serialized when `message` is `tx` or `block`.
+    pub fn new(
+        message: impl Into<String>,
+        ccode: RejectCode,
+        reason: impl Into<String>,
+        hash: Option<Hash>,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            ccode,
+            reason: reason.into(),
+            hash,
+        }
+    }
+
+    /// Returns the length of the serialized [`RejectData`], in bytes.
+    pub fn serialized_len(&self) -> usize {
+        var_bytes_len(self.message.as_bytes())
+            + 1
+            + var_bytes_len(self.reason.as_bytes())
+            + if has_hash(&self.message) {
+                HASH_SIZE
+            } else {
+                0
+            }
+    }
+}
+
+/// Returns `true` if rejects of `message` carry a hash.
+fn has_hash(message: &str) -> bool {
+    matches!(message, "tx" | "block")
+}
+
+impl Display for RejectData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "`{}` rejected ({}): {}",
+            self.message, self.ccode, self.reason
+        )
+    }
+}
+
+impl BitcoinSerialize for RejectData {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(self.serialized_len());
+        write_var_bytes(&mut buf, self.message.as_bytes())?;
+        buf.write_u8(self.ccode as u8)?;
+        write_var_bytes(&mut buf, self.reason.as_bytes())?;
+        if has_hash(&self.message) {
+            write_array(&mut buf, self.hash.unwrap_or_default().as_bytes())?;
+        }
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for RejectData {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let message = String::from_utf8(read_var_bytes(data)?)?;
+        let ccode = RejectCode::try_from(data.read_u8()?)?;
+        let reason = String::from_utf8(read_var_bytes(data)?)?;
+        let hash = match has_hash(&message) {
+            true => Some(Hash::from_bytes(data)?),
+            false => None,
+        };
+
+        Ok(Self {
+            message,
+            ccode,
+            reason,
+            hash,
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::utils::roundtrip_test;
+    use alloc::string::ToString;
+    use hex_literal::hex;
+    use quickcheck::Arbitrary;
+
+    impl Arbitrary for RejectData {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let message = g
+                .choose(&["version", "tx", "block", "foo"])
+                .unwrap()
+                .to_string();
+            let hash = has_hash(&message).then(|| Hash::arbitrary(g));
+
+            Self::new(
+                message,
+                RejectCode::arbitrary(g),
+                String::arbitrary(g),
+                hash,
+            )
+        }
+    }
+
+    roundtrip_test!(reject_data_roundtrip, RejectData);
+
+    #[test]
+    fn reject_of_version_decodes() {
+        let data = hex!("0776657273696f6e1117637573746f6d2072656a65637420666f72207465737473");
+
+        let reject = RejectData::from_bytes(&mut data.as_slice()).unwrap();
+
+        assert_eq!(
+            reject,
+            RejectData::new(
+                "version",
+                RejectCode::Obsolete,
+                "custom reject for tests",
+                None
+            )
+        );
+        assert_eq!(
+            reject.to_string(),
+            "`version` rejected (obsolete): custom reject for tests"
+        );
+    }
+
+    #[test]
+    fn reject_of_tx_has_hash() {
+        let hash = Hash::from([0x42; HASH_SIZE]);
+        let reject = RejectData::new("tx", RejectCode::Dust, "dust", Some(hash));
+
+        let bytes = reject.to_bytes().unwrap();
+        let decoded = RejectData::from_bytes(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(bytes.len(), 3 + 1 + 5 + HASH_SIZE);
+        assert_eq!(*decoded.hash(), Some(hash));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    errors::BitcoinMessageError,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    message::{BitcoinDeserialize, BitcoinSerialize, MAX_SIZE},
+};
+use alloc::{vec, vec::Vec};
+use byteorder::LittleEndian;
+use core::{fmt::Display, str::FromStr};
+
+/// Size of [`Hash`], in bytes.
+pub const HASH_SIZE: usize = 32;
+
+/// A double-SHA256 hash, identifying e.g. a block or a transaction.
+///
+/// The bytes are stored in the wire (internal) byte order, while [`Display`] and [`FromStr`] use the byte-reversed hex
+/// representation known from block explorers and RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Hash([u8; HASH_SIZE]);
+
+impl Hash {
+    /// Creates new [`Hash`] from bytes in the wire byte order.
+    pub const fn new(bytes: [u8; HASH_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the bytes of this hash, in the wire byte order.
+    pub const fn as_bytes(&self) -> &[u8; HASH_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; HASH_SIZE]> for Hash {
+    fn from(bytes: [u8; HASH_SIZE]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Display for Hash {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for b in self.0.iter().rev() {
+            write!(f, "{:02x}", b)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Hash {
+    type Err = BitcoinMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != HASH_SIZE * 2 || !s.is_ascii() {
+            return Err(BitcoinMessageError::InvalidHashString);
+        }
+        let mut bytes = [0u8; HASH_SIZE];
+        for (i, b) in bytes.iter_mut().rev().enumerate() {
+            *b = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)
+                .map_err(|_| BitcoinMessageError::InvalidHashString)?;
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
+impl BitcoinSerialize for Hash {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        Ok(self.0.to_vec())
+    }
+}
+
+impl BitcoinDeserialize for Hash {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        Ok(Self(read_array(data)?))
+    }
+}
+
+/// Variable length integer, used to prefix lists and strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VarInt(pub u64);
+
+impl VarInt {
+    /// Returns the length of the serialized [`VarInt`], in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub const fn len(&self) -> usize {
+        match self.0 {
+            0..=0xfc => 1,
+            0xfd..=0xffff => 3,
+            0x10000..=0xffffffff => 5,
+            _ => 9,
+        }
+    }
+}
+
+impl From<usize> for VarInt {
+    fn from(n: usize) -> Self {
+        Self(n as u64)
+    }
+}
+
+impl BitcoinSerialize for VarInt {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        let mut buf = Vec::with_capacity(self.len());
+        match self.0 {
+            0..=0xfc => buf.write_u8(self.0 as u8)?,
+            0xfd..=0xffff => {
+                buf.write_u8(0xfd)?;
+                buf.write_u16::<LittleEndian>(self.0 as u16)?;
+            }
+            0x10000..=0xffffffff => {
+                buf.write_u8(0xfe)?;
+                buf.write_u32::<LittleEndian>(self.0 as u32)?;
+            }
+            _ => {
+                buf.write_u8(0xff)?;
+                buf.write_u64::<LittleEndian>(self.0)?;
+            }
+        }
+
+        Ok(buf)
+    }
+}
+
+impl BitcoinDeserialize for VarInt {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        let n = match data.read_u8()? {
+            0xfd => data.read_u16::<LittleEndian>()? as u64,
+            0xfe => data.read_u32::<LittleEndian>()? as u64,
+            0xff => data.read_u64::<LittleEndian>()?,
+            x => x as u64,
+        };
+
+        Ok(Self(n))
+    }
+}
+
+impl<const N: usize> BitcoinSerialize for [u8; N] {
+    fn to_bytes(&self) -> Result<Vec<u8>, BitcoinMessageError> {
+        Ok(self.to_vec())
+    }
+}
+
+impl<const N: usize> BitcoinDeserialize for [u8; N] {
+    fn from_bytes(data: &mut impl Read) -> Result<Self, BitcoinMessageError>
+    where
+        Self: core::marker::Sized,
+    {
+        read_array(data)
+    }
+}
+
+/// Reads exactly `N` bytes, e.g. a [`Hash`]. Running out of data is a [`BitcoinMessageError::SerializationError`]
+/// with the `UnexpectedEof` kind.
+pub(crate) fn read_array<const N: usize>(
+    data: &mut impl Read,
+) -> Result<[u8; N], BitcoinMessageError> {
+    let mut bytes = [0u8; N];
+    data.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Writes `bytes` as they are, without a length prefix.
+pub(crate) fn write_array<const N: usize>(
+    buf: &mut impl Write,
+    bytes: &[u8; N],
+) -> Result<(), BitcoinMessageError> {
+    buf.write_all(bytes)?;
+
+    Ok(())
+}
+
+/// Returns the serialized length of `bytes` prefixed with their length as a [`VarInt`].
+pub(crate) fn var_bytes_len(bytes: &[u8]) -> usize {
+    VarInt::from(bytes.len()).len() + bytes.len()
+}
+
+/// Writes `bytes` prefixed with their length as a [`VarInt`].
+pub(crate) fn write_var_bytes(
+    buf: &mut impl Write,
+    bytes: &[u8],
+) -> Result<(), BitcoinMessageError> {
+    buf.write_all(&VarInt::from(bytes.len()).to_bytes()?)?;
+    buf.write_all(bytes)?;
+
+    Ok(())
+}
+
+/// Reads bytes prefixed with their length as a [`VarInt`].
+pub(crate) fn read_var_bytes(data: &mut impl Read) -> Result<Vec<u8>, BitcoinMessageError> {
+    let len = VarInt::from_bytes(data)?.0 as usize;
+    if len > MAX_SIZE {
+        return Err(BitcoinMessageError::PayloadTooBig {
+            size: len,
+            max: MAX_SIZE,
+        });
+    }
+    let mut bytes = vec![0u8; len];
+    data.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Writes `items` prefixed with their count as a [`VarInt`]. Fails with the error made by `too_large` from the count
+/// if there are more than `max` items.
+pub(crate) fn write_vec<T: BitcoinSerialize>(
+    buf: &mut impl Write,
+    items: &[T],
+    max: usize,
+    too_large: fn(usize) -> BitcoinMessageError,
+) -> Result<(), BitcoinMessageError> {
+    if items.len() > max {
+        return Err(too_large(items.len()));
+    }
+    buf.write_all(&VarInt::from(items.len()).to_bytes()?)?;
+    for item in items {
+        buf.write_all(&item.to_bytes()?)?;
+    }
+
+    Ok(())
+}
+
+/// Reads items prefixed with their count as a [`VarInt`]. Fails with the error made by `too_large` from the count if
+/// there are more than `max` items, before reading any of them.
+pub(crate) fn read_vec<T: BitcoinDeserialize>(
+    data: &mut impl Read,
+    max: usize,
+    too_large: fn(usize) -> BitcoinMessageError,
+) -> Result<Vec<T>, BitcoinMessageError> {
+    let count = VarInt::from_bytes(data)?.0 as usize;
+    if count > max {
+        return Err(too_large(count));
+    }
+    (0..count).map(|_| T::from_bytes(data)).collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use hex_literal::hex;
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn arrays_roundtrip() {
+        let short_id = hex!("0102030405ff");
+        let hash = [0x42; HASH_SIZE];
+        let mut buf = Vec::new();
+
+        write_array(&mut buf, &short_id).unwrap();
+        write_array(&mut buf, &hash).unwrap();
+
+        assert_eq!(buf.len(), 6 + HASH_SIZE);
+        let mut data = buf.as_slice();
+        assert_eq!(read_array::<6>(&mut data).unwrap(), short_id);
+        assert_eq!(<[u8; HASH_SIZE]>::from_bytes(&mut data).unwrap(), hash);
+        assert_eq!(short_id.to_bytes().unwrap(), short_id);
+    }
+
+    #[test]
+    fn short_array_read_is_unexpected_eof() {
+        let data = [0u8; 5];
+
+        for result in [
+            read_array::<6>(&mut data.as_slice()).map(|_| ()),
+            read_array::<HASH_SIZE>(&mut data.as_slice()).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(BitcoinMessageError::SerializationError(e))
+                    if is_unexpected_eof(&e)
+            ));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn is_unexpected_eof(e: &crate::io::Error) -> bool {
+        e.kind() == std::io::ErrorKind::UnexpectedEof
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn is_unexpected_eof(e: &crate::io::Error) -> bool {
+        *e == crate::io::Error::UnexpectedEof
+    }
+
+    impl Arbitrary for Hash {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let mut bytes = [0u8; HASH_SIZE];
+            bytes.iter_mut().for_each(|b| *b = u8::arbitrary(g));
+
+            Self(bytes)
+        }
+    }
+
+    #[quickcheck]
+    fn varint_roundtrip(n: u64) -> bool {
+        let bytes = VarInt(n).to_bytes().unwrap();
+
+        bytes.len() == VarInt(n).len()
+            && VarInt::from_bytes(&mut bytes.as_slice()).unwrap() == VarInt(n)
+    }
+
+    #[test]
+    fn varint_boundaries() {
+        assert_eq!(VarInt(0xfc).to_bytes().unwrap(), hex!("fc"));
+        assert_eq!(VarInt(0xfd).to_bytes().unwrap(), hex!("fdfd00"));
+        assert_eq!(VarInt(0x10000).to_bytes().unwrap(), hex!("fe00000100"));
+        assert_eq!(
+            VarInt(0x100000000).to_bytes().unwrap(),
+            hex!("ff0000000001000000")
+        );
+    }
+
+    fn too_large(count: usize) -> BitcoinMessageError {
+        BitcoinMessageError::InventoryTooLarge { count }
+    }
+
+    #[test]
+    fn vec_empty_and_single() {
+        let hash = Hash::new([0xab; HASH_SIZE]);
+        for items in [vec![], vec![hash]] {
+            let mut buf = Vec::new();
+            write_vec(&mut buf, &items, 1, too_large).unwrap();
+
+            assert_eq!(buf.len(), 1 + items.len() * HASH_SIZE);
+            assert_eq!(buf[0], items.len() as u8);
+            assert_eq!(
+                read_vec::<Hash>(&mut buf.as_slice(), 1, too_large).unwrap(),
+                items
+            );
+        }
+    }
+
+    #[test]
+    fn vec_over_limit() {
+        let items = vec![Hash::default(); 3];
+        let mut buf = Vec::new();
+
+        assert!(matches!(
+            write_vec(&mut buf, &items, 2, too_large),
+            Err(BitcoinMessageError::InventoryTooLarge { count: 3 })
+        ));
+        assert!(buf.is_empty());
+
+        // only the count, the items must not be read
+        let data = hex!("03");
+        assert!(matches!(
+            read_vec::<Hash>(&mut data.as_slice(), 2, too_large),
+            Err(BitcoinMessageError::InventoryTooLarge { count: 3 })
+        ));
+    }
+
+    #[quickcheck]
+    fn hash_string_roundtrip(h: Hash) -> bool {
+        h.to_string().parse::<Hash>().unwrap() == h
+    }
+
+    #[test]
+    fn hash_is_displayed_reversed() {
+        let mut bytes = [0u8; HASH_SIZE];
+        bytes[0] = 0x01;
+
+        assert_eq!(
+            Hash::new(bytes).to_string(),
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+    }
+
+    #[test]
+    fn invalid_hash_string() {
+        assert!(matches!(
+            "00".parse::<Hash>(),
+            Err(BitcoinMessageError::InvalidHashString)
+        ));
+        assert!(matches!(
+            "zz00000000000000000000000000000000000000000000000000000000000000".parse::<Hash>(),
+            Err(BitcoinMessageError::InvalidHashString)
+        ));
+    }
+}
@@ -2,24 +2,77 @@ use sha2::{Digest, Sha256};
 
 pub const CHECKSUM_SIZE: usize = 4;
 
-/// Computes Bitcoin checksum for given data
-pub fn checksum(data: &[u8]) -> [u8; 4] {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let hash = hasher.finalize();
+/// Checksum of an empty payload, precomputed to skip hashing when sending `verack` and the like.
+pub const EMPTY_PAYLOAD_CHECKSUM: [u8; CHECKSUM_SIZE] = [0x5d, 0xf6, 0xe0, 0xe2];
 
-    let mut hasher = Sha256::new();
-    hasher.update(hash);
-    let hash = hasher.finalize();
+/// Computes SHA256(SHA256(data)), used e.g. for block hashes
+pub fn double_sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
 
+/// Computes Bitcoin checksum for given data
+pub fn checksum(data: &[u8]) -> [u8; 4] {
     let mut buf = [0u8; CHECKSUM_SIZE];
-    buf.clone_from_slice(&hash[..CHECKSUM_SIZE]);
+    buf.copy_from_slice(&double_sha256(data)[..CHECKSUM_SIZE]);
 
     buf
 }
 
+/// Generates the quickcheck tests every payload type should have, in a module called `$name`: a serialization
+/// roundtrip, `to_bytes` not panicking, and `serialized_len` matching the serialized bytes.
+///
+/// `$t` has to implement [`Arbitrary`](quickcheck::Arbitrary) and [`PartialEq`].
+#[cfg(test)]
+macro_rules! roundtrip_test {
+    ($name:ident, $t:ty) => {
+        mod $name {
+            use super::*;
+            use $crate::message::{BitcoinDeserialize, BitcoinSerialize};
+
+            #[quickcheck_macros::quickcheck]
+            fn roundtrip(x: $t) -> bool {
+                let bytes = x.to_bytes().unwrap();
+
+                <$t>::from_bytes(&mut bytes.as_slice()).unwrap() == x
+            }
+
+            #[quickcheck_macros::quickcheck]
+            fn to_bytes_fuzz(x: $t) {
+                let _ = x.to_bytes();
+            }
+
+            #[quickcheck_macros::quickcheck]
+            fn serialized_len_matches_bytes(x: $t) -> bool {
+                x.serialized_len() == x.to_bytes().unwrap().len()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use roundtrip_test;
+
+/// Generates an address as it survives the wire format, for the [`Arbitrary`](quickcheck::Arbitrary) impls.
+///
+/// IPv4 and IPv6 addresses are generated equally often, so both are covered by every roundtrip property. IPv4-mapped
+/// addresses decode as IPv4, and IPv6 flow info and scope id aren't sent.
+#[cfg(test)]
+pub(crate) fn arbitrary_wire_addr(g: &mut quickcheck::Gen) -> core::net::SocketAddr {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use quickcheck::Arbitrary;
+
+    let ip = if bool::arbitrary(g) {
+        IpAddr::V4(Ipv4Addr::from(u32::arbitrary(g)))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(u128::arbitrary(g))).to_canonical()
+    };
+
+    (ip, u16::arbitrary(g)).into()
+}
+
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
     use quickcheck_macros::quickcheck;
 
     use super::*;
@@ -29,9 +82,28 @@ mod tests {
         let _ = checksum(&data);
     }
 
+    #[quickcheck]
+    fn checksum_is_truncated_double_sha256(data: Vec<u8>) -> bool {
+        checksum(&data) == double_sha256(&data)[..CHECKSUM_SIZE]
+    }
+
+    #[test]
+    fn arbitrary_wire_addr_covers_both_families() {
+        let mut g = quickcheck::Gen::new(100);
+        let addrs: Vec<_> = (0..100).map(|_| arbitrary_wire_addr(&mut g)).collect();
+
+        assert!(addrs.iter().any(|a| a.is_ipv4()));
+        assert!(addrs.iter().any(|a| a.is_ipv6()));
+    }
+
     #[test]
     fn checksum_of_empty_data() {
         let data = vec![];
         assert_eq!(checksum(&data), [0x5d, 0xf6, 0xe0, 0xe2]);
     }
+
+    #[test]
+    fn empty_payload_checksum_is_precomputed_correctly() {
+        assert_eq!(EMPTY_PAYLOAD_CHECKSUM, checksum(&[]));
+    }
 }
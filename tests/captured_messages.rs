@@ -0,0 +1,56 @@
+//! Decodes every message in `tests/captured/` and `tests/vectors/`, and checks it re-encodes to the exact same bytes.
+//!
+//! Each `<command>[-<variant>].hex` file holds one full message as hex, with its source in `#` comment lines.
+//! `tests/captured/` only holds messages as sent on the wire by real nodes. `tests/vectors/` holds payloads taken
+//! from documentation or the chain itself, wrapped in a computed header.
+
+#![allow(clippy::unwrap_used)]
+
+use bitcoin_handshake::*;
+use std::{fs, path::Path};
+
+fn parse_hex(text: &str) -> Vec<u8> {
+    let digits: String = text
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .flat_map(|l| l.chars().filter(|c| !c.is_whitespace()))
+        .collect();
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn assert_roundtrip(dir: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let mut count = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with('#'), "{}: source not documented", name);
+        let bytes = parse_hex(&text);
+
+        let message = Message::from_bytes_strict(&mut bytes.as_slice())
+            .unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+        let command = name.split('-').next().unwrap();
+        assert_eq!(message.command().as_str(), command, "{}", name);
+        assert_eq!(message.network(), Some(Network::Mainnet), "{}", name);
+        assert_eq!(message.to_bytes().unwrap(), bytes, "{}", name);
+        count += 1;
+    }
+
+    assert!(count > 0, "no messages found in {}", dir.display());
+}
+
+#[test]
+fn captured_messages_roundtrip() {
+    assert_roundtrip("tests/captured");
+}
+
+#[test]
+fn reference_vectors_roundtrip() {
+    assert_roundtrip("tests/vectors");
+}
@@ -0,0 +1,61 @@
+//! Decodes a `version` message captured from a live Bitcoin Core 0.17.1 mainnet peer through [`MessageReader`].
+//!
+//! The bytes are the complete message, header included, as a `/Satoshi:0.17.1/` node sent it on 2019-01-27 (its
+//! `timestamp`). The capture is not ours: it comes from the `deserialize_version_test` test of rust-bitcoin
+//! (CC0-1.0), `src/p2p/message.rs` in the `bitcoin` 0.32.5 crate. Unlike the roundtrip tests, these bytes were
+//! produced by Core itself, so they check that decoding, and the re-encoding, agree with the real thing.
+
+#![cfg(feature = "std")]
+#![allow(clippy::unwrap_used)]
+
+use bitcoin_handshake::*;
+use hex_literal::hex;
+use std::net::{IpAddr, Ipv4Addr};
+
+const CORE_VERSION: [u8; 126] = hex!(
+    "f9beb4d976657273696f6e000000000066000000be61b8277f1101000d04000000000000"
+    "f00f4d5c00000000000000000000000000000000000000000000ffff5bf08c80b4bd0d04"
+    "000000000000000000000000000000000000000000000000faa99559cc68a1c1102f5361"
+    "746f7368693a302e31372e312f938c080001"
+);
+
+#[test]
+fn core_version_decodes() {
+    let config = ReaderConfig {
+        strict: true,
+        ..Default::default()
+    };
+    let mut reader = MessageReader::with_config(CORE_VERSION.as_slice(), config);
+
+    let version = reader.read_message().unwrap();
+    assert_eq!(*version.command(), Command::Version);
+    assert_eq!(version.network(), Some(Network::Mainnet));
+    let data = version.payload().as_version().unwrap();
+    assert_eq!(*data.version(), 70015);
+    assert_eq!(
+        *data.services(),
+        ServiceIdentifier::NODE_NETWORK
+            | ServiceIdentifier::NODE_BLOOM
+            | ServiceIdentifier::NODE_WITNESS
+            | ServiceIdentifier::NODE_NETWORK_LIMITED
+    );
+    assert_eq!(*data.timestamp(), 1548554224);
+    assert_eq!(
+        data.addr_recv_socket_address().ip(),
+        IpAddr::V4(Ipv4Addr::new(91, 240, 140, 128))
+    );
+    assert_eq!(
+        *data.addr_trans_socket_address(),
+        UNSPECIFIED_SOCKET_ADDRESS
+    );
+    assert_eq!(*data.nonce(), 13952548347456104954);
+    assert_eq!(data.user_agent(), "/Satoshi:0.17.1/");
+    assert_eq!(*data.start_height(), 560275);
+    assert!(*data.relay());
+    assert_eq!(version.to_bytes().unwrap(), CORE_VERSION);
+
+    assert!(matches!(
+        reader.read_message(),
+        Err(BitcoinMessageError::ConnectionClosed)
+    ));
+}
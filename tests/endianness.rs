@@ -0,0 +1,139 @@
+//! Byte-level layout of serialized messages.
+//!
+//! Bitcoin encodes integers as little-endian, except for IP addresses and ports, which use network (big-endian) byte
+//! order. Each test serializes a value with distinctive byte patterns and asserts the bytes at fixed offsets, so that
+//! an endianness mistake fails here rather than on the wire. New message types should add their assertions here.
+
+#![allow(clippy::unwrap_used)]
+
+use bitcoin_handshake::*;
+use hex_literal::hex;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+/// Offset of the payload in a serialized [`Message`].
+const PAYLOAD: usize = 24;
+
+fn version_data() -> VersionData {
+    VersionData::with_nonce(
+        0x0102030405060708,
+        ServiceIdentifier::NODE_NETWORK | ServiceIdentifier::NODE_WITNESS,
+        0x1112131415161718,
+        ServiceIdentifier::NODE_BLOOM,
+        SocketAddr::new(
+            IpAddr::V6(Ipv6Addr::new(
+                0x2001, 0x0db8, 0x0102, 0x0304, 0x0506, 0x0708, 0x090a, 0x0b0c,
+            )),
+            0x2021,
+        ),
+        ServiceIdentifier::NODE_NETWORK_LIMITED,
+        SocketAddr::new(IpAddr::V4([192, 168, 1, 2].into()), 0x3031),
+        "/x/".to_string(),
+        0x41424344,
+        true,
+    )
+}
+
+#[test]
+fn header_layout() {
+    let message = Message::new(START_STRING_MAINNET, Command::Ping, Payload::Ping(0));
+    let bytes = message.to_bytes().unwrap();
+
+    assert_eq!(bytes[0..4], hex!("f9beb4d9"));
+    assert_eq!(bytes[4..16], *b"ping\0\0\0\0\0\0\0\0");
+    // payload length is a little-endian u32
+    assert_eq!(bytes[16..20], hex!("08000000"));
+    assert_eq!(bytes[20..24], message.payload_checksum().unwrap());
+}
+
+#[test]
+fn version_data_layout() {
+    let bytes = version_data().to_bytes().unwrap();
+
+    assert_eq!(bytes[0..4], PROTOCOL_VERSION.to_le_bytes());
+    assert_eq!(bytes[4..12], hex!("0900000000000000"));
+    assert_eq!(bytes[12..20], hex!("1817161514131211"));
+    // addr_recv: services LE, address and port BE
+    assert_eq!(bytes[20..28], hex!("0400000000000000"));
+    assert_eq!(bytes[28..44], hex!("20010db80102030405060708090a0b0c"));
+    assert_eq!(bytes[44..46], hex!("2021"));
+    // addr_trans: IPv4 is sent as IPv4-mapped IPv6
+    assert_eq!(bytes[46..54], hex!("0004000000000000"));
+    assert_eq!(bytes[54..70], hex!("00000000000000000000ffffc0a80102"));
+    assert_eq!(bytes[70..72], hex!("3031"));
+    assert_eq!(bytes[72..80], hex!("0807060504030201"));
+    assert_eq!(bytes[80..84], hex!("032f782f"));
+    assert_eq!(bytes[84..88], hex!("44434241"));
+    assert_eq!(bytes[88..], hex!("01"));
+}
+
+#[test]
+fn version_data_layout_survives_roundtrip() {
+    let x = version_data();
+    let bytes = x.to_bytes().unwrap();
+
+    let y = VersionData::from_bytes(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(y.addr_recv_socket_address(), x.addr_recv_socket_address());
+    assert_eq!(y.addr_trans_socket_address().port(), 0x3031);
+    assert_eq!(y.timestamp(), x.timestamp());
+    assert_eq!(y.nonce(), x.nonce());
+    assert_eq!(y.start_height(), x.start_height());
+}
+
+#[test]
+fn ping_pong_layout() {
+    for (command, payload) in [
+        (Command::Ping, Payload::Ping(0x0102030405060708)),
+        (Command::Pong, Payload::Pong(0x0102030405060708)),
+    ] {
+        let bytes = Message::new(START_STRING_MAINNET, command, payload)
+            .to_bytes()
+            .unwrap();
+
+        assert_eq!(bytes[PAYLOAD..], hex!("0807060504030201"));
+    }
+}
+
+#[test]
+fn cf_checkpt_layout() {
+    let mut stop_hash = [0u8; HASH_SIZE];
+    stop_hash[0] = 0x01;
+    let filter_headers = vec![Hash::new([0x11; HASH_SIZE]); 0xfd];
+    let message = Message::new(
+        START_STRING_MAINNET,
+        Command::CfCheckpt,
+        Payload::CfCheckpt(CfCheckpt::new(0, Hash::new(stop_hash), filter_headers)),
+    );
+    let bytes = message.to_bytes().unwrap();
+
+    // hashes are sent in the internal byte order, not the reversed display order
+    assert_eq!(bytes[PAYLOAD + 1], 0x01);
+    // the header count is a little-endian VarInt
+    assert_eq!(bytes[PAYLOAD + 33..PAYLOAD + 36], hex!("fdfd00"));
+}
+
+#[test]
+fn send_cmpct_layout() {
+    let bytes = Message::new(
+        START_STRING_MAINNET,
+        Command::SendCmpct,
+        Payload::SendCmpct(SendCmpct::new(true, 0x0102030405060708)),
+    )
+    .to_bytes()
+    .unwrap();
+
+    assert_eq!(bytes[PAYLOAD..], hex!("010807060504030201"));
+}
+
+#[test]
+fn fee_filter_layout() {
+    let bytes = Message::new(
+        START_STRING_MAINNET,
+        Command::FeeFilter,
+        Payload::FeeFilter(0x0102030405060708),
+    )
+    .to_bytes()
+    .unwrap();
+
+    assert_eq!(bytes[PAYLOAD..], hex!("0807060504030201"));
+}